@@ -0,0 +1,233 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use sdk::*;
+use sdk::anyhow::anyhow;
+use crate::{Counts, Game};
+
+// How the elf draws the cubes for a single pull. Cubes always go back in the bag between pulls, so
+// pulls are independent of each other; this only decides whether a cube can show up twice in one pull.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sampling {
+    // Each cube is put back before the next one is drawn (multinomial)
+    WithReplacement,
+    // A handful is drawn at once (multivariate hypergeometric)
+    WithoutReplacement,
+}
+
+impl Sampling {
+    pub const ALL: [Sampling; 2] = [Sampling::WithReplacement, Sampling::WithoutReplacement];
+
+    // Smallest number of cubes of a color the bag can hold and still produce the observed pulls
+    fn min_count(self, max_pulled: u32) -> u32 {
+        match self {
+            Sampling::WithReplacement => max_pulled.min(1),
+            Sampling::WithoutReplacement => max_pulled,
+        }
+    }
+
+    // Natural log of the probability of drawing exactly `pull` from `bag`
+    fn ln_probability(self, ln_factorial: &LnFactorial, bag: &Counts, pull: &Counts) -> f64 {
+        let bag_total = bag.total();
+        let pull_total = pull.total();
+        match self {
+            Sampling::WithReplacement => {
+                let mut ln_p = ln_factorial.get(pull_total);
+                for (color, pulled) in &pull.0 {
+                    if *pulled == 0 {
+                        continue;
+                    }
+                    let available = bag.get(color);
+                    if available == 0 {
+                        return f64::NEG_INFINITY;
+                    }
+                    ln_p -= ln_factorial.get(*pulled);
+                    ln_p += *pulled as f64 * (available as f64 / bag_total as f64).ln();
+                }
+                ln_p
+            }
+            Sampling::WithoutReplacement => {
+                if pull_total > bag_total {
+                    return f64::NEG_INFINITY;
+                }
+                let mut ln_p = -ln_factorial.choose(bag_total, pull_total);
+                for (color, pulled) in &pull.0 {
+                    let available = bag.get(color);
+                    if *pulled > available {
+                        return f64::NEG_INFINITY;
+                    }
+                    ln_p += ln_factorial.choose(available, *pulled);
+                }
+                ln_p
+            }
+        }
+    }
+}
+
+impl FromStr for Sampling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "with" | "with-replacement" => Ok(Sampling::WithReplacement),
+            "without" | "without-replacement" => Ok(Sampling::WithoutReplacement),
+            _ => Err(anyhow!("Unknown sampling `{s}`; expected `with` or `without`")),
+        }
+    }
+}
+
+impl Display for Sampling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sampling::WithReplacement => write!(f, "with replacement"),
+            Sampling::WithoutReplacement => write!(f, "without replacement"),
+        }
+    }
+}
+
+// ln(n!) for every n up to the largest bag we'll consider
+#[derive(Debug, Clone)]
+struct LnFactorial(Vec<f64>);
+
+impl LnFactorial {
+    fn new(max: u32) -> Self {
+        let mut table = Vec::with_capacity(max as usize + 1);
+        table.push(0.0);
+        for n in 1..=max {
+            table.push(table[n as usize - 1] + (n as f64).ln());
+        }
+        LnFactorial(table)
+    }
+
+    fn get(&self, n: u32) -> f64 {
+        self.0[n as usize]
+    }
+
+    // ln(n choose k), for k <= n
+    fn choose(&self, n: u32, k: u32) -> f64 {
+        self.get(n) - self.get(k) - self.get(n - k)
+    }
+}
+
+// The most likely bag for a game, and how likely its pulls are under each sampling model
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub game: Game,
+    pub bag: Counts,
+    pub ln_likelihoods: Vec<(Sampling, f64)>,
+}
+
+impl Display for Analysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Game {}: most likely bag {}", self.game.0, self.bag)?;
+        for (sampling, ln_likelihood) in &self.ln_likelihoods {
+            write!(f, "; likelihood {:.3e} {sampling}", ln_likelihood.exp())?;
+        }
+        Ok(())
+    }
+}
+
+// Finds the bag with exactly `budget` cubes of the `colors` (a zeroed `Counts`) that makes the
+// observed pulls most likely under `sampling`. Every split of the budget between colors is tried, so
+// this is only suitable for puzzle-sized bags. `None` if no bag of that size can produce the pulls.
+pub fn analyze(game: Game, pulls: &[Counts], colors: &Counts, budget: u32, sampling: Sampling) -> Option<Analysis> {
+    let ln_factorial = LnFactorial::new(budget.max(pulls.iter().map(Counts::total).max().unwrap_or(0)));
+    let min_bag = pulls.iter().fold(colors.clone(), |a, b| a.max(b));
+    let min_counts: Vec<(&str, u32)> = min_bag.0
+        .iter()
+        .map(|(color, max_pulled)| (color.as_str(), sampling.min_count(*max_pulled)))
+        .collect();
+    let required: u32 = min_counts.iter().map(|(_, min)| min).sum();
+    if required > budget {
+        debug!("Game {}: needs at least {required} cubes {sampling}, budget is {budget}", game.0);
+        return None;
+    }
+
+    let mut best: Option<(Counts, f64)> = None;
+    let mut bag = Counts::default();
+    for_each_bag(&min_counts, budget - required, &mut bag, &mut |bag| {
+        let ln_likelihood = ln_likelihood(&ln_factorial, bag, pulls, sampling);
+        trace!("Game {}: {bag} has log-likelihood {ln_likelihood}", game.0);
+        if best.as_ref().map(|(_, best)| ln_likelihood > *best).unwrap_or(ln_likelihood.is_finite()) {
+            best = Some((bag.clone(), ln_likelihood));
+        }
+    });
+
+    let (bag, _) = best?;
+    let ln_likelihoods = Sampling::ALL
+        .iter()
+        .map(|sampling| (*sampling, ln_likelihood(&ln_factorial, &bag, pulls, *sampling)))
+        .collect();
+    Some(Analysis { game, bag, ln_likelihoods })
+}
+
+// Natural log of the probability of observing every pull from `bag`
+fn ln_likelihood(ln_factorial: &LnFactorial, bag: &Counts, pulls: &[Counts], sampling: Sampling) -> f64 {
+    pulls
+        .iter()
+        .map(|pull| sampling.ln_probability(ln_factorial, bag, pull))
+        .sum()
+}
+
+// Calls `f` with every bag that has at least the minimum count of each color and `spare` extra cubes
+fn for_each_bag(min_counts: &[(&str, u32)], spare: u32, bag: &mut Counts, f: &mut impl FnMut(&Counts)) {
+    match min_counts {
+        [] => {}
+        [(color, min)] => {
+            bag.0.insert(color.to_string(), min + spare);
+            f(bag);
+        }
+        [(color, min), rest @ ..] => {
+            for extra in 0..=spare {
+                bag.0.insert(color.to_string(), min + extra);
+                for_each_bag(rest, spare - extra, bag, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Counts, Game};
+    use crate::feasibility::{analyze, LnFactorial, Sampling};
+
+    #[test]
+    fn probabilities() {
+        let ln_factorial = LnFactorial::new(10);
+        let bag = Counts::of([("red", 2), ("blue", 2)]);
+        let cases = [
+            (Sampling::WithReplacement, Counts::of([("red", 1)]), 0.5),
+            (Sampling::WithoutReplacement, Counts::of([("red", 1)]), 0.5),
+            (Sampling::WithReplacement, Counts::of([("red", 2)]), 0.25),
+            (Sampling::WithoutReplacement, Counts::of([("red", 2)]), 1.0 / 6.0),
+            (Sampling::WithReplacement, Counts::of([("red", 1), ("blue", 1)]), 0.5),
+            (Sampling::WithoutReplacement, Counts::of([("red", 1), ("blue", 1)]), 4.0 / 6.0),
+            (Sampling::WithReplacement, Counts::of([("red", 3)]), 0.125),
+            (Sampling::WithoutReplacement, Counts::of([("red", 3)]), 0.0),
+        ];
+        for (sampling, pull, expected) in cases {
+            let p = sampling.ln_probability(&ln_factorial, &bag, &pull).exp();
+            assert!((p - expected).abs() < 1e-9, "{pull} {sampling}: {p} != {expected}");
+        }
+    }
+
+    #[test]
+    fn most_likely_bag() {
+        let colors = Counts::of([("red", 0), ("green", 0), ("blue", 0)]);
+        let pulls = [
+            Counts::of([("red", 2), ("blue", 2)]),
+            Counts::of([("red", 2), ("blue", 2)]),
+        ];
+
+        // Never seeing green means the most likely bag has none
+        let analysis = analyze(Game(1), &pulls, &colors, 6, Sampling::WithReplacement).unwrap();
+        assert_eq!(analysis.bag, Counts::of([("red", 3), ("green", 0), ("blue", 3)]));
+        let analysis = analyze(Game(1), &pulls, &colors, 6, Sampling::WithoutReplacement).unwrap();
+        assert_eq!(analysis.bag, Counts::of([("red", 3), ("green", 0), ("blue", 3)]));
+
+        // Drawing 4 at once without replacement needs at least 4 cubes, with replacement only 2
+        assert!(analyze(Game(1), &pulls, &colors, 3, Sampling::WithoutReplacement).is_none());
+        let analysis = analyze(Game(1), &pulls, &colors, 2, Sampling::WithReplacement).unwrap();
+        assert_eq!(analysis.bag, Counts::of([("red", 1), ("green", 0), ("blue", 1)]));
+        assert_eq!(analysis.ln_likelihoods[1], (Sampling::WithoutReplacement, f64::NEG_INFINITY));
+    }
+}
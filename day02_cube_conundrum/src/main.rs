@@ -1,67 +1,197 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
 use std::iter::Sum;
-use std::ops::{Add, Sub};
+use std::ops::Add;
 use std::str::FromStr;
 use sdk::{debug, info, init, lines, trace};
 use sdk::*;
 use sdk::anyhow::anyhow;
+use crate::feasibility::Sampling;
+
+mod feasibility;
+
+// Used when neither `--limits` nor `BAG_LIMITS` is given
+const DEFAULT_LIMITS: &str = "red=12,green=13,blue=14";
 
 fn main() -> Result<()> {
     init();
+    let args = Args::parse(std::env::args().skip(1))?;
+    let bag = Bag::new(args.limits()?);
+    info!("Bag: {bag}");
+
     let mut games: BTreeMap<Game, Vec<Counts>> = BTreeMap::new();
     for line in lines("day02_cube_conundrum/input.txt")? {
-        let line = line?;
-        let (game, pulls) = Counts::parse_line(&line).ok_or(anyhow!("Unable to parse counts from {line}"))?;
+        let (game, pulls) = Counts::parse_line(&line, &bag.colors())?;
         games.insert(game, pulls);
     }
 
     let min_bag_size: BTreeMap<Game, Counts> = games
         .iter()
-        .map(|(game, counts)| {
-            let bag = counts
-                .iter()
-                .copied()
-                .fold(Counts::default(), |a, b| a.max(b));
-            (*game, bag)
-        }).collect();
-
-    let sum_of_min_powers: i32 = min_bag_size
-        .into_iter()
-        .map(|(_, bag)| bag.power())
+        .map(|(game, counts)| (*game, min_bag(&bag, counts)))
+        .collect();
+
+    let sum_of_min_powers: u64 = min_bag_size
+        .into_values()
+        .map(|min_bag| min_bag.power())
         .sum();
 
     let valid_game_count: usize = games.iter()
-        .filter(|(_, counts)| counts.iter().all(|c| is_valid(*c)))
+        .filter(|(_, counts)| counts.iter().all(|c| bag.is_valid(c)))
         .map(|(game, _)| game.0)
         .sum();
 
     info!("Valid game ID sum: {valid_game_count}");
     info!("Minimum pag power: {sum_of_min_powers:?}");
+
+    let budget = args.budget.unwrap_or(bag.limits.total());
+    let sampling = args.sampling.unwrap_or(Sampling::WithoutReplacement);
+    let mut infeasible = Vec::new();
+    for (game, pulls) in &games {
+        match feasibility::analyze(*game, pulls, &bag.limits.zeroed(), budget, sampling) {
+            Some(analysis) => debug!("{analysis}"),
+            None => infeasible.push(game.0),
+        }
+    }
+    info!("Most likely bags of {budget} cubes {sampling} found for {} games", games.len() - infeasible.len());
+    info!("Games impossible with {budget} cubes {sampling}: {infeasible:?}");
     Ok(())
 }
 
+// Command line options:
+//   --limits red=12,green=13,blue=14   bag limits, falling back to the `BAG_LIMITS` environment
+//                                      variable (which may be set in `.env`), then the puzzle's bag
+//   --budget 39                        total cubes for the most likely bag (defaults to the limits' total)
+//   --sampling with|without            how cubes are drawn within a pull (defaults to without)
+#[derive(Debug, Clone, Default)]
+struct Args {
+    limits: Option<Counts>,
+    budget: Option<u32>,
+    sampling: Option<Sampling>,
+}
 
-fn is_valid(counts: Counts) -> bool {
-    const MAX_COUNTS: Counts = Counts {
-        green: 13,
-        red: 12,
-        blue: 14,
-    };
-    let diff = MAX_COUNTS - counts;
+impl Args {
+    fn parse(mut args: impl Iterator<Item=String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(anyhow!("Missing value for {arg}"));
+            match arg.as_str() {
+                "--limits" => parsed.limits = Some(Counts::parse_limits(&value()?)?),
+                "--budget" => parsed.budget = Some(u32::from_str(&value()?)?),
+                "--sampling" => parsed.sampling = Some(Sampling::from_str(&value()?)?),
+                other => return Err(anyhow!("Unexpected argument `{other}`")),
+            }
+        }
+        Ok(parsed)
+    }
 
-    // If we counted more than our max of any color, this game doesn't count
-    if diff.red < 0 || diff.blue < 0 || diff.green < 0 {
-        debug!("{counts:?} has single color greater than max {MAX_COUNTS:?}");
-        return false;
+    fn limits(&self) -> Result<Counts> {
+        if let Some(limits) = &self.limits {
+            return Ok(limits.clone());
+        }
+        match std::env::var("BAG_LIMITS") {
+            Ok(limits) => Counts::parse_limits(&limits),
+            Err(_) => Counts::parse_limits(DEFAULT_LIMITS),
+        }
     }
+}
+
+// The smallest bag that could have produced every pull in a game
+fn min_bag(bag: &Bag, pulls: &[Counts]) -> Counts {
+    pulls
+        .iter()
+        .fold(bag.limits.zeroed(), |a, b| a.max(b))
+}
 
-    // If the total number of blocks pulled is greater than our max, doesn' count
-    if diff.total() > MAX_COUNTS.total() {
-        debug!("{counts:?} has total count {} greater than max {MAX_COUNTS:?} {}", diff.total(), MAX_COUNTS.total());
-        return false;
+#[derive(Debug, Clone)]
+struct Bag {
+    limits: Counts,
+    rules: Vec<Rule>,
+}
+
+impl Bag {
+    fn new(limits: Counts) -> Self {
+        Bag { limits, rules: Rule::ALL.to_vec() }
     }
 
-    true
+    fn colors(&self) -> BTreeSet<&str> {
+        self.limits.colors().collect()
+    }
+
+    fn violations(&self, pull: &Counts) -> Vec<Violation> {
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.check(&self.limits, pull))
+            .collect()
+    }
+
+    fn is_valid(&self, pull: &Counts) -> bool {
+        let violations = self.violations(pull);
+        for violation in &violations {
+            debug!("{pull:?} is not possible: {violation}");
+        }
+        violations.is_empty()
+    }
+}
+
+impl Display for Bag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (rules: {:?})", self.limits, self.rules)
+    }
+}
+
+// A check that a single pull must pass to be possible with a given bag
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Rule {
+    // A pull can't contain more cubes of any color than the bag holds of that color
+    ColorLimit,
+    // A pull can't contain more cubes than the bag holds in total
+    TotalLimit,
+}
+
+impl Rule {
+    const ALL: [Rule; 2] = [Rule::ColorLimit, Rule::TotalLimit];
+
+    fn check(self, limits: &Counts, pull: &Counts) -> Vec<Violation> {
+        match self {
+            Rule::ColorLimit => pull.0
+                .iter()
+                .filter(|(color, count)| **count > limits.get(color))
+                .map(|(color, count)| Violation::Color {
+                    color: color.clone(),
+                    pulled: *count,
+                    limit: limits.get(color),
+                })
+                .collect(),
+            Rule::TotalLimit => {
+                let pulled = pull.total();
+                let limit = limits.total();
+                if pulled > limit {
+                    vec![Violation::Total { pulled, limit }]
+                } else {
+                    vec![]
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Violation {
+    Color { color: String, pulled: u32, limit: u32 },
+    Total { pulled: u32, limit: u32 },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Color { color, pulled, limit } => {
+                write!(f, "pulled {pulled} {color} but the bag only holds {limit}")
+            }
+            Violation::Total { pulled, limit } => {
+                write!(f, "pulled {pulled} cubes but the bag only holds {limit}")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Ord, Eq)]
@@ -69,46 +199,39 @@ struct Game(usize);
 
 impl Game {
     // Game 1
-    fn parse(game: &str) -> Option<Self> {
+    fn parse(game: &str) -> Result<Self> {
         trace!("Parsing game from {game}");
-        let (game, number) = game.split_once(" ")?;
-        if game != "Game" {
-            return None;
+        let (header, number) = game.split_once(' ').ok_or(anyhow!("Unable to split `{game}` into header and number"))?;
+        if header != "Game" {
+            return Err(anyhow!("Header `{header}` is not 'Game'"));
         }
-        let game = Game(usize::from_str(number).ok()?);
+        let game = Game(usize::from_str(number)?);
         trace!("Parsed {game:?}");
-        Some(game)
+        Ok(game)
     }
 }
 
-#[derive(Debug, Copy, Clone, Default)]
-struct Counts {
-    green: i32,
-    red: i32,
-    blue: i32,
-}
+// Number of cubes by color
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Counts(BTreeMap<String, u32>);
 
 impl Add for Counts {
     type Output = Counts;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Counts {
-            green: self.green + rhs.green,
-            red: self.red + rhs.red,
-            blue: self.blue + rhs.blue,
-        }
+        &self + &rhs
     }
 }
 
-impl<'a> Add for &'a Counts {
+impl Add for &Counts {
     type Output = Counts;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Counts {
-            green: self.green + rhs.green,
-            red: self.red + rhs.red,
-            blue: self.blue + rhs.blue,
+        let mut sum = self.clone();
+        for (color, count) in &rhs.0 {
+            *sum.0.entry(color.clone()).or_default() += count;
         }
+        sum
     }
 }
 
@@ -118,67 +241,160 @@ impl Sum for Counts {
     }
 }
 
-impl Sub for Counts {
-    type Output = Counts;
-
-    fn sub(self, rhs: Self) -> Self::Output {
-        Counts {
-            green: self.green - rhs.green,
-            red: self.red - rhs.red,
-            blue: self.blue - rhs.blue,
-        }
+impl Display for Counts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let counts: Vec<_> = self.0.iter().map(|(color, count)| format!("{count} {color}")).collect();
+        write!(f, "{}", counts.join(", "))
     }
 }
 
 impl Counts {
     // Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
-    fn parse_line(line: &str) -> Option<(Game, Vec<Self>)> {
+    fn parse_line(line: &str, colors: &BTreeSet<&str>) -> Result<(Game, Vec<Self>)> {
         trace!("Parsing line {line}");
 
         // 3 red
-        fn parse_pull(pull: &str) -> Option<Counts> {
+        fn parse_pull(pull: &str, colors: &BTreeSet<&str>) -> Result<Counts> {
             trace!("Parsing pull from {pull}");
-            let (number, color) = pull.split_once(" ")?;
-            let number = i32::from_str(number).ok()?;
-            let mut pull = Counts::default();
-            match color {
-                "red" => pull.red = number,
-                "blue" => pull.blue = number,
-                "green" => pull.green = number,
-                _ => {}
+            let (number, color) = pull.split_once(' ').ok_or(anyhow!("Unable to split `{pull}` into number and color"))?;
+            if !colors.contains(color) {
+                return Err(anyhow!("Unknown color `{color}` in pull `{pull}`; expected one of {colors:?}"));
             }
+            let pull = Counts::of([(color, u32::from_str(number)?)]);
             trace!("Parsed {pull:?}");
-            Some(pull)
+            Ok(pull)
         }
 
-        let (game, pulls) = line.split_once(": ")?;
+        let (game, pulls) = line.split_once(": ").ok_or(anyhow!("Unable to split `{line}` into game and pulls"))?;
         let game = Game::parse(game)?;
         let pulls = pulls
             .split("; ")
             .map(|g| {
                 g.split(", ")
-                    .filter_map(parse_pull)
+                    .map(|pull| parse_pull(pull, colors))
                     .sum()
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
         trace!("Parsed line {line} to {game:?}, {pulls:?}");
-        Some((game, pulls))
+        Ok((game, pulls))
     }
 
-    fn total(&self) -> i32 {
-        self.red + self.green + self.blue
+    // red=12,green=13,blue=14
+    fn parse_limits(limits: &str) -> Result<Self> {
+        let mut counts = Counts::default();
+        for limit in limits.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            let (color, count) = limit.split_once('=').ok_or(anyhow!("Unable to parse bag limit `{limit}`; expected `color=count`"))?;
+            if counts.0.insert(color.trim().to_owned(), u32::from_str(count.trim())?).is_some() {
+                return Err(anyhow!("Duplicate bag limit for `{color}`"));
+            }
+        }
+        if counts.0.is_empty() {
+            return Err(anyhow!("No bag limits found in `{limits}`"));
+        }
+        Ok(counts)
+    }
+
+    fn of<'a>(counts: impl IntoIterator<Item=(&'a str, u32)>) -> Self {
+        Counts(counts.into_iter().map(|(color, count)| (color.to_owned(), count)).collect())
     }
 
-    fn max(self, other: Self) -> Self {
-        Counts {
-            green: self.green.max(other.green),
-            red: self.red.max(other.red),
-            blue: self.blue.max(other.blue),
+    fn colors(&self) -> impl Iterator<Item=&str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    fn get(&self, color: &str) -> u32 {
+        self.0.get(color).copied().unwrap_or(0)
+    }
+
+    // The same colors, with no cubes of any of them
+    fn zeroed(&self) -> Self {
+        Counts(self.0.keys().map(|color| (color.clone(), 0)).collect())
+    }
+
+    fn total(&self) -> u32 {
+        self.0.values().sum()
+    }
+
+    fn max(mut self, other: &Self) -> Self {
+        for (color, count) in &other.0 {
+            let current = self.0.entry(color.clone()).or_default();
+            *current = (*current).max(*count);
+        }
+        self
+    }
+
+    fn power(&self) -> u64 {
+        self.0.values().map(|c| *c as u64).product()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use crate::{Bag, Counts, min_bag, Rule, Violation};
+
+    fn puzzle_bag() -> Bag {
+        Bag::new(Counts::parse_limits("red=12,green=13,blue=14").unwrap())
+    }
+
+    #[test]
+    fn color_limit() {
+        let limits = Counts::of([("red", 2), ("blue", 1)]);
+        let cases = [
+            (Counts::of([("red", 2)]), vec![]),
+            (Counts::of([("red", 2), ("blue", 1)]), vec![]),
+            (Counts::of([("red", 3)]), vec![Violation::Color { color: "red".to_owned(), pulled: 3, limit: 2 }]),
+            (Counts::of([("red", 3), ("blue", 2)]), vec![
+                Violation::Color { color: "blue".to_owned(), pulled: 2, limit: 1 },
+                Violation::Color { color: "red".to_owned(), pulled: 3, limit: 2 },
+            ]),
+        ];
+        for (pull, expected) in cases {
+            assert_eq!(Rule::ColorLimit.check(&limits, &pull), expected, "{pull}");
+        }
+    }
+
+    #[test]
+    fn total_limit() {
+        let limits = Counts::of([("red", 2), ("blue", 1)]);
+        let cases = [
+            (Counts::of([("red", 3)]), vec![]),
+            (Counts::of([("blue", 3)]), vec![]),
+            (Counts::of([("red", 2), ("blue", 2)]), vec![Violation::Total { pulled: 4, limit: 3 }]),
+        ];
+        for (pull, expected) in cases {
+            assert_eq!(Rule::TotalLimit.check(&limits, &pull), expected, "{pull}");
         }
     }
 
-    fn power(self) -> i32 {
-        self.red * self.blue * self.green
+    #[test]
+    fn parse_errors() {
+        let colors = BTreeSet::from(["red", "green", "blue"]);
+        assert!(Counts::parse_line("Game 1: 3 blue, 4 red", &colors).is_ok());
+        assert!(Counts::parse_line("Game 1: 3 blue, 4 purple", &colors).is_err());
+        assert!(Counts::parse_line("Game 1: 3 blue, four red", &colors).is_err());
+        assert!(Counts::parse_line("Round 1: 3 blue", &colors).is_err());
+        assert!(Counts::parse_limits("red=12,red=13").is_err());
+        assert!(Counts::parse_limits("red:12").is_err());
+        assert!(Counts::parse_limits("").is_err());
+    }
+
+    #[test]
+    fn example() {
+        let bag = puzzle_bag();
+        let games: Vec<_> = include_str!("../example.txt")
+            .lines()
+            .map(|line| Counts::parse_line(line, &bag.colors()).unwrap())
+            .collect();
+
+        let valid: usize = games.iter()
+            .filter(|(_, pulls)| pulls.iter().all(|p| bag.is_valid(p)))
+            .map(|(game, _)| game.0)
+            .sum();
+        assert_eq!(valid, 8);
+
+        let power: u64 = games.iter().map(|(_, pulls)| min_bag(&bag, pulls).power()).sum();
+        assert_eq!(power, 2286);
     }
 }
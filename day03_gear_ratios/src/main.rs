@@ -1,6 +1,8 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
+use std::io::IsTerminal;
 use std::str::FromStr;
+use colored::Colorize;
 use sdk::*;
 
 fn main() -> Result<()> {
@@ -8,26 +10,21 @@ fn main() -> Result<()> {
     let input = std::fs::read_to_string("day03_gear_ratios/input.txt")?;
     let schematic = Schematic::parse(&input)?;
 
+    // `--render` prints the annotated schematic; colors are only used when stdout is a terminal
+    if std::env::args().skip(1).any(|arg| arg == "--render") {
+        print!("{}", schematic.render(std::io::stdout().is_terminal()));
+    }
+
     let part_numbers: usize = schematic
-        .neighbors()
-        .iter()
-        .filter_map(|(element, neighbors)| {
-            element.element.number().filter(|_| neighbors.iter().any(|e| e.is_symbol()))
-        })
+        .part_numbers()
+        .filter_map(|element| element.element.number())
         .sum();
 
-    let gear_ratios: usize = schematic.neighbors().iter().filter_map(|(element, neighbors)| {
-        element.element
-            .symbol()
-            .filter(|c| *c == '*')
-            .and_then(|_| {
-                let numeric_neighbors: Vec<_> = neighbors.iter().filter_map(|n| n.element.number()).collect();
-                match numeric_neighbors.as_slice() {
-                    &[a, b] => Some(a * b),
-                    _ => None,
-                }
-            })
-    }).sum();
+    let gear_ratios: usize = schematic
+        .gears(GearDefinition::PUZZLE)
+        .iter()
+        .map(|gear| gear.ratio)
+        .sum();
 
     info!("Sum of part numbers: {part_numbers}");
     info!("Sum of gear ratios: {gear_ratios}");
@@ -36,28 +33,28 @@ fn main() -> Result<()> {
 
 #[derive(Debug, Clone)]
 struct Schematic {
-    data: Vec<SchematicData>,
-    rows: Vec<Vec<Option<SchematicData>>>,
+    elements: Vec<SchematicData>,
+    // Index into `elements` of whatever occupies each cell
+    grid: Vec<Vec<Option<usize>>>,
+    // Indices into `elements` of everything adjacent (including diagonals) to each element
+    adjacency: Vec<Vec<usize>>,
     num_rows: usize,
     num_cols: usize,
 }
 
 impl Schematic {
     fn parse(input: &str) -> Result<Self> {
-        let mut data = Vec::new();
-        let mut rows = Vec::new();
-        for line in input.lines() {
-            println!("{line}");
-        }
+        let mut elements = Vec::new();
+        let mut grid = Vec::new();
         for (row, line) in input.lines().enumerate() {
             let mut current_row = Vec::new();
             let mut current = TokenBuffer::default();
             for (col, char) in line.chars().enumerate() {
                 current_row.push(None);
-                if char.is_numeric() {
+                if char.is_ascii_digit() {
                     current.push(char, col);
                 } else {
-                    Self::finish_number(&mut data, &mut current_row, &mut current, row)?;
+                    Self::finish_number(&mut elements, &mut current_row, &mut current, row)?;
                     if char != '.' {
                         let element = SchematicData {
                             element: SchematicElement::Symbol(char),
@@ -65,28 +62,30 @@ impl Schematic {
                             start: col,
                             end: col + 1,
                         };
-                        data.push(element);
-                        current_row[col] = Some(element);
+                        current_row[col] = Some(elements.len());
+                        elements.push(element);
                     }
                 }
-
-                if col + 1 == line.len() {
-                    Self::finish_number(&mut data, &mut current_row, &mut current, row)?;
-                }
             }
-            rows.push(current_row);
+            Self::finish_number(&mut elements, &mut current_row, &mut current, row)?;
+            grid.push(current_row);
         }
-        let schematic = Schematic {
-            num_rows: rows.len(),
-            num_cols: rows[0].len(),
-            data,
-            rows,
+        let num_cols = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut schematic = Schematic {
+            num_rows: grid.len(),
+            num_cols,
+            elements,
+            grid,
+            adjacency: Vec::new(),
         };
+        schematic.adjacency = (0..schematic.elements.len())
+            .map(|i| schematic.find_neighbors(&schematic.elements[i]))
+            .collect();
         trace!("Loaded schematic: {schematic:?}");
         Ok(schematic)
     }
 
-    fn finish_number(data: &mut Vec<SchematicData>, row: &mut Vec<Option<SchematicData>>, number: &mut TokenBuffer, row_index: usize) -> Result<()> {
+    fn finish_number(elements: &mut Vec<SchematicData>, row: &mut [Option<usize>], number: &mut TokenBuffer, row_index: usize) -> Result<()> {
         if let Some((number, start)) = number.take() {
             let element = SchematicData {
                 element: SchematicElement::Number(usize::from_str(&number)?),
@@ -94,120 +93,169 @@ impl Schematic {
                 start,
                 end: start + number.len(),
             };
-            data.push(element);
-            for i in start..start + number.len() {
-                row[i] = Some(element);
+            for cell in &mut row[start..start + number.len()] {
+                *cell = Some(elements.len());
             }
+            elements.push(element);
         }
         Ok(())
     }
 
-    fn neighbors(&self) -> Vec<(SchematicData, Vec<SchematicData>)> {
-        self.data.iter().map(|feature| {
-            let element = feature.element;
-            let mut neighbors = BTreeSet::new();
-
-            let column_before = (feature.start > 0).then(|| feature.start - 1);
-            let column_after = (feature.end + 1 < self.num_cols).then(|| feature.end);
-
-            let row_above = (feature.row > 0).then(|| feature.row - 1);
-            let row_below = (feature.row + 1 < self.num_rows).then(|| feature.row + 1);
-
-            let mut add_neighbor = |row: usize, col: usize| {
-                if let Some(element) = self.rows[row][col] {
-                    neighbors.insert(element);
-                }
-            };
-
-            if let Some(column) = column_before {
-                add_neighbor(feature.row, column);
-            }
-
-            if let Some(column) = column_after {
-                add_neighbor(feature.row, column);
-            }
-
-            if let Some(row) = row_above {
-                let start = column_before.unwrap_or(feature.start);
-                let end = column_after.unwrap_or(feature.end - 1);
-                for column in start..=end {
-                    add_neighbor(row, column);
+    // Only used while building the index; everything else should go through `neighbors`
+    fn find_neighbors(&self, feature: &SchematicData) -> Vec<usize> {
+        let mut neighbors = BTreeSet::new();
+        let rows = feature.row.saturating_sub(1)..=(feature.row + 1).min(self.num_rows - 1);
+        let columns = feature.start.saturating_sub(1)..=feature.end.min(self.num_cols - 1);
+        for row in rows {
+            for column in columns.clone() {
+                let occupant = self.grid[row].get(column).copied().flatten();
+                if let Some(index) = occupant {
+                    if self.elements[index] != *feature {
+                        neighbors.insert(index);
+                    }
                 }
             }
+        }
+        trace!("{} (row {}, col {}) neighbors: {neighbors:?}", feature.element, feature.row, feature.start);
+        neighbors.into_iter().collect()
+    }
 
-            if let Some(row) = row_below {
-                let start = column_before.unwrap_or(feature.start);
-                let end = column_after.unwrap_or(feature.end - 1);
-                for column in start..=end {
-                    add_neighbor(row, column);
-                }
-            }
+    fn elements(&self) -> impl Iterator<Item=(usize, &SchematicData)> {
+        self.elements.iter().enumerate()
+    }
 
-            trace!("{element} (row {}, col {}) does not match", feature.row, feature.start);
-            (*feature, neighbors.into_iter().collect::<Vec<_>>())
-        }).collect()
+    fn neighbors(&self, index: usize) -> impl Iterator<Item=&SchematicData> {
+        self.adjacency[index].iter().map(|i| &self.elements[*i])
     }
 
     // A number is a part number if it is adjascent (including diagonals) to a symbol
-    #[allow(unused)]
-    fn part_numbers(&self) -> Vec<SchematicData> {
-        self.data.iter().filter(|element| {
-            let number = match element.element {
-                SchematicElement::Number(n) => n,
-                SchematicElement::Symbol(_) => return false,
-            };
+    fn part_numbers(&self) -> impl Iterator<Item=&SchematicData> {
+        self.elements()
+            .filter(|(i, element)| element.is_number() && self.neighbors(*i).any(|n| n.is_symbol()))
+            .map(|(_, element)| element)
+    }
 
-            let column_before = (element.start > 0).then(|| element.start - 1);
-            let column_after = (element.end + 1 < self.num_cols).then(|| element.end);
+    // Numbers next to at least one `symbol`
+    #[allow(unused)]
+    fn numbers_adjacent_to(&self, symbol: char) -> Vec<&SchematicData> {
+        self.elements()
+            .filter(|(i, element)| {
+                element.is_number() && self.neighbors(*i).any(|n| n.element.symbol() == Some(symbol))
+            })
+            .map(|(_, element)| element)
+            .collect()
+    }
 
-            let row_above = (element.row > 0).then(|| element.row - 1);
-            let row_below = (element.row + 1 < self.num_rows).then(|| element.row + 1);
+    // Symbols next to at least one occurrence of `number`
+    #[allow(unused)]
+    fn symbols_adjacent_to(&self, number: usize) -> Vec<&SchematicData> {
+        self.elements()
+            .filter(|(i, element)| {
+                element.is_symbol() && self.neighbors(*i).any(|n| n.element.number() == Some(number))
+            })
+            .map(|(_, element)| element)
+            .collect()
+    }
 
-            let is_symbol = |row: usize, col: usize| {
-                let element = self.rows[row][col];
-                element.map(|d| d.is_symbol()).unwrap_or(false)
-            };
+    fn gears(&self, definition: GearDefinition) -> Vec<Gear> {
+        self.elements()
+            .filter(|(_, element)| element.element.symbol() == Some(definition.symbol))
+            .filter_map(|(i, element)| {
+                let numbers: Vec<_> = self.neighbors(i).filter_map(|n| n.element.number()).collect();
+                (numbers.len() == definition.numbers).then(|| Gear {
+                    symbol: *element,
+                    ratio: numbers.iter().product(),
+                    numbers,
+                })
+            })
+            .collect()
+    }
 
-            if let Some(column) = column_before {
-                if is_symbol(element.row, column) {
-                    trace!("{number} (row {}, col {}) matches on previous column", element.row, element.start);
-                    return true;
+    // Draws the schematic with part numbers, non-part numbers and gears told apart, followed on each
+    // row by the ratios of the gears in it. Without color, non-part numbers are listed after the row
+    // instead of being highlighted.
+    fn render(&self, color: bool) -> String {
+        let parts: BTreeSet<_> = self.part_numbers().map(|e| (e.row, e.start)).collect();
+        let gears: BTreeMap<_, _> = self.gears(GearDefinition::PUZZLE)
+            .into_iter()
+            .map(|gear| ((gear.symbol.row, gear.symbol.start), gear))
+            .collect();
+
+        let mut rendered = String::new();
+        for (row, cells) in self.grid.iter().enumerate() {
+            let mut not_parts = Vec::new();
+            let mut col = 0;
+            while col < cells.len() {
+                let Some(index) = cells[col] else {
+                    rendered.push('.');
+                    col += 1;
+                    continue;
+                };
+                let element = self.elements[index];
+                let text = match element.element {
+                    SchematicElement::Number(n) => format!("{n:0width$}", width = element.end - element.start),
+                    SchematicElement::Symbol(c) => c.to_string(),
+                };
+                let key = (element.row, element.start);
+                let styled = match element.element {
+                    SchematicElement::Number(_) if parts.contains(&key) => text.green().bold(),
+                    SchematicElement::Number(n) => {
+                        not_parts.push(n);
+                        text.red().dimmed()
+                    }
+                    SchematicElement::Symbol(_) if gears.contains_key(&key) => text.black().on_yellow(),
+                    SchematicElement::Symbol(_) => text.yellow(),
+                };
+                if color {
+                    rendered.push_str(&styled.to_string());
+                } else {
+                    rendered.push_str(&text);
                 }
+                col = element.end;
             }
 
-            if let Some(column) = column_after {
-                if is_symbol(element.row, column) {
-                    trace!("{number} (row {}, col {}) matches on following column", element.row, element.start);
-                    return true;
-                }
+            let mut annotations: Vec<_> = gears
+                .range((row, 0)..(row + 1, 0))
+                .map(|((_, col), gear)| {
+                    let numbers: Vec<_> = gear.numbers.iter().map(|n| n.to_string()).collect();
+                    format!("gear at {col}: {} = {}", numbers.join(" * "), gear.ratio)
+                })
+                .collect();
+            if !color && !not_parts.is_empty() {
+                let not_parts: Vec<_> = not_parts.iter().map(|n| n.to_string()).collect();
+                annotations.push(format!("not parts: {}", not_parts.join(", ")));
             }
-
-            if let Some(row) = row_above {
-                let start = column_before.unwrap_or(element.start);
-                let end = column_after.unwrap_or(element.end - 1);
-                for column in start..=end {
-                    if is_symbol(row, column) {
-                        trace!("{number} (row {}, col {}) matches on previous row, column {column}", element.row, element.start);
-                        return true;
-                    }
+            if !annotations.is_empty() {
+                let annotations = annotations.join("; ");
+                rendered.push_str("  ");
+                if color {
+                    rendered.push_str(&annotations.cyan().to_string());
+                } else {
+                    rendered.push_str(&annotations);
                 }
             }
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
 
-            if let Some(row) = row_below {
-                let start = column_before.unwrap_or(element.start);
-                let end = column_after.unwrap_or(element.end - 1);
-                for column in start..=end {
-                    if is_symbol(row, column) {
-                        trace!("{number} (row {}, col {}) matches on following row, column {column}", element.row, element.start);
-                        return true;
-                    }
-                }
-            }
+// Which symbols count as gears: a `symbol` with exactly `numbers` adjacent numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GearDefinition {
+    symbol: char,
+    numbers: usize,
+}
 
-            trace!("{number} (row {}, col {}) does not match", element.row, element.start);
-            false
-        }).copied().collect()
-    }
+impl GearDefinition {
+    const PUZZLE: GearDefinition = GearDefinition { symbol: '*', numbers: 2 };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Gear {
+    symbol: SchematicData,
+    numbers: Vec<usize>,
+    ratio: usize,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -238,10 +286,11 @@ struct SchematicData {
 
 impl SchematicData {
     fn is_symbol(&self) -> bool {
-        match self.element {
-            SchematicElement::Symbol(_) => true,
-            _ => false,
-        }
+        self.element.is_symbol()
+    }
+
+    fn is_number(&self) -> bool {
+        self.element.is_number()
     }
 }
 
@@ -266,20 +315,12 @@ impl SchematicElement {
         }
     }
 
-    #[allow(unused)]
     fn is_symbol(&self) -> bool {
-        match self {
-            SchematicElement::Symbol(_) => true,
-            _ => false,
-        }
+        matches!(self, SchematicElement::Symbol(_))
     }
 
-    #[allow(unused)]
     fn is_number(&self) -> bool {
-        match self {
-            SchematicElement::Symbol(_) => true,
-            _ => false,
-        }
+        matches!(self, SchematicElement::Number(_))
     }
 }
 
@@ -290,4 +331,68 @@ impl fmt::Display for SchematicElement {
             SchematicElement::Symbol(c) => write!(f, "{c}"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GearDefinition, Schematic, SchematicElement};
+
+    fn example() -> Schematic {
+        Schematic::parse(include_str!("../example.txt")).unwrap()
+    }
+
+    #[test]
+    fn predicates() {
+        assert!(SchematicElement::Number(1).is_number());
+        assert!(!SchematicElement::Number(1).is_symbol());
+        assert!(SchematicElement::Symbol('*').is_symbol());
+        assert!(!SchematicElement::Symbol('*').is_number());
+    }
+
+    #[test]
+    fn part_numbers() {
+        let schematic = example();
+        let sum: usize = schematic.part_numbers().filter_map(|e| e.element.number()).sum();
+        assert_eq!(sum, 4361);
+
+        // Numbers touching the last column count too
+        let schematic = Schematic::parse("..12\n...#").unwrap();
+        let parts: Vec<_> = schematic.part_numbers().filter_map(|e| e.element.number()).collect();
+        assert_eq!(parts, vec![12]);
+    }
+
+    #[test]
+    fn queries() {
+        let schematic = example();
+        let mut numbers: Vec<_> = schematic.numbers_adjacent_to('*').iter().filter_map(|e| e.element.number()).collect();
+        numbers.sort();
+        assert_eq!(numbers, vec![35, 467, 598, 617, 755]);
+
+        let symbols: Vec<_> = schematic.symbols_adjacent_to(633).iter().map(|e| (e.element, e.row, e.start)).collect();
+        assert_eq!(symbols, vec![(SchematicElement::Symbol('#'), 3, 6)]);
+        assert!(schematic.symbols_adjacent_to(114).is_empty());
+    }
+
+    #[test]
+    fn gears() {
+        let schematic = example();
+        let ratios: Vec<_> = schematic.gears(GearDefinition::PUZZLE).iter().map(|g| g.ratio).collect();
+        assert_eq!(ratios, vec![16345, 451490]);
+
+        let single = schematic.gears(GearDefinition { symbol: '*', numbers: 1 });
+        assert_eq!(single.iter().map(|g| g.numbers.clone()).collect::<Vec<_>>(), vec![vec![617]]);
+
+        let plus = schematic.gears(GearDefinition { symbol: '+', numbers: 1 });
+        assert_eq!(plus.iter().map(|g| g.ratio).collect::<Vec<_>>(), vec![592]);
+    }
+
+    #[test]
+    fn render_plain() {
+        let rendered = example().render(false);
+        let lines: Vec<_> = rendered.lines().collect();
+        assert_eq!(lines[0], "467....114  not parts: 114");
+        assert_eq!(lines[1], "...*......  gear at 3: 467 * 35 = 16345");
+        assert_eq!(lines[3], "......#...");
+        assert_eq!(lines.len(), 10);
+    }
+}
@@ -1,47 +1,181 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use sdk::*;
 use sdk::anyhow::anyhow;
 
 fn main() -> Result<()> {
     init();
+    let prize_rule = parse_args(std::env::args().skip(1))?;
     let mut cards = Vec::new();
     for line in lines("day04_scratchcards/input.txt")? {
-        let line = line?;
         let card = Card::parse(&line)?;
         cards.push(card);
     }
     debug!("Cards: {cards:?}");
     let points: usize = cards.iter().map(|c| c.points()).sum();
     info!("Points: {points}");
-    let card_tally = tally(&cards);
+    let card_tally = tally(&cards, &Standard)?;
     info!("Card counts: {card_tally}");
+    if let Some((name, rule)) = prize_rule {
+        let simulated = tally(&cards, rule.as_ref())?;
+        info!("Card counts ({name} prize rule): {simulated}");
+    }
     Ok(())
 }
 
-fn tally(cards: &[Card]) -> usize {
-    // card #, count
-    let mut collected: BTreeMap<usize, usize> = BTreeMap::new();
+// `--prize-rule capped|points` additionally simulates the deck under an alternative prize rule
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<Option<(String, Box<dyn PrizeRule>)>> {
+    let mut prize_rule = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prize-rule" => {
+                let name = args.next().ok_or(anyhow!("Missing value for --prize-rule"))?;
+                let rule: Box<dyn PrizeRule> = match name.as_str() {
+                    "standard" => Box::new(Standard),
+                    "capped" => Box::new(Capped),
+                    "points" => Box::new(PointsBased),
+                    other => return Err(anyhow!("Unknown prize rule `{other}`; expected standard, capped or points")),
+                };
+                prize_rule = Some((name, rule));
+            }
+            other => return Err(anyhow!("Unexpected argument `{other}`")),
+        }
+    }
+    Ok(prize_rule)
+}
+
+fn tally(cards: &[Card], rule: &dyn PrizeRule) -> Result<usize> {
+    validate(cards)?;
+
+    // Copies of the card at each index. Each card adds its count to a contiguous run of following
+    // cards, so rather than touching every card in the run we record where the run starts and stops
+    // applying and keep a running total of everything currently applying (`carry`).
+    let overflow = |card: &Card| anyhow!("Card count overflowed at card {}", card.number);
+    let mut counts = vec![0_usize; cards.len()];
+    let mut starts = vec![0_usize; cards.len() + 1];
+    let mut stops = vec![0_usize; cards.len() + 1];
+    let mut carry = 0_usize;
+    for (i, card) in cards.iter().enumerate() {
+        carry = carry.checked_add(starts[i]).ok_or_else(|| overflow(card))? - stops[i];
+        counts[i] = carry.checked_add(1).ok_or_else(|| overflow(card))?;
+
+        let mut copies = rule.copies(card);
+        let remaining = cards.len() - i - 1;
+        if copies > remaining {
+            if !rule.capped() {
+                return Err(anyhow!("Card {} wins {copies} copies but only {remaining} cards follow it", card.number));
+            }
+            copies = remaining;
+        }
+        trace!("Card {}: {} collected, wins copies of the next {copies}", card.number, counts[i]);
+
+        if copies > 0 {
+            starts[i + 1] = starts[i + 1].checked_add(counts[i]).ok_or_else(|| overflow(card))?;
+            stops[i + 1 + copies] += counts[i];
+        }
+    }
+    counts.iter().try_fold(0_usize, |sum, count| sum.checked_add(*count)).ok_or(anyhow!("Total card count overflowed"))
+}
+
+// Cards must be numbered 1, 2, 3, ... in order, since prizes are won by position
+fn validate(cards: &[Card]) -> std::result::Result<(), DeckError> {
+    // Only numbers up to the number of cards can be valid, so only those get a slot in the table;
+    // any larger ones (however large) are tracked separately to spot duplicates
+    let max = cards.iter().map(|c| c.number).max().unwrap_or(0).min(cards.len());
+    let mut seen = vec![false; max + 1];
+    let mut beyond = BTreeSet::new();
     for card in cards {
-        let num_collected = {
-            let mut num_collected = collected.entry(card.number).or_default();
-            *num_collected += 1;
-            *num_collected
+        let duplicate = match seen.get_mut(card.number) {
+            Some(seen) => std::mem::replace(seen, true),
+            None => !beyond.insert(card.number),
         };
+        if duplicate {
+            return Err(DeckError::Duplicate(card.number));
+        }
+    }
+    // With no duplicates, any number past the number of cards leaves one of these missing
+    if let Some(missing) = (1..=max).find(|n| !seen[*n]) {
+        return Err(DeckError::Gap(missing));
+    }
+    if seen[0] {
+        return Err(DeckError::Gap(0));
+    }
+    if let Some((position, card)) = cards.iter().enumerate().find(|(i, c)| c.number != i + 1) {
+        return Err(DeckError::OutOfOrder { position, number: card.number });
+    }
+    Ok(())
+}
 
-        let matches = card.count_matches();
-        if matches > 0 {
-            let copies: Vec<_> = (0..matches).map(|i| i + 1 + card.number).collect();
-            trace!("Card {}: {matches} matches, wins copies of {copies:?}", card.number);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeckError {
+    Duplicate(usize),
+    // A missing card number (or card 0, which can't be won by any card)
+    Gap(usize),
+    OutOfOrder { position: usize, number: usize },
+}
 
-            for card_num in copies {
-                (*collected.entry(card_num).or_default()) += num_collected;
+impl Display for DeckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeckError::Duplicate(number) => write!(f, "Card {number} appears more than once"),
+            DeckError::Gap(0) => write!(f, "Card numbers must start at 1"),
+            DeckError::Gap(number) => write!(f, "Card {number} is missing"),
+            DeckError::OutOfOrder { position, number } => {
+                write!(f, "Card {number} is out of order (found at position {})", position + 1)
             }
-        } else {
-            trace!("Card {}: {matches} matches", card.number);
         }
     }
-    collected.values().sum()
+}
+
+impl std::error::Error for DeckError {}
+
+// Decides how many of the following cards a card wins a copy of
+trait PrizeRule {
+    fn copies(&self, card: &Card) -> usize;
+
+    // Whether copies past the last card are dropped rather than treated as an error
+    fn capped(&self) -> bool {
+        false
+    }
+}
+
+// The puzzle's rule: one copy of each of the next `matches` cards, never past the end of the deck
+#[derive(Debug, Clone, Copy)]
+struct Standard;
+
+impl PrizeRule for Standard {
+    fn copies(&self, card: &Card) -> usize {
+        card.count_matches()
+    }
+}
+
+// Like `Standard`, but copies past the end of the deck are silently dropped
+#[derive(Debug, Clone, Copy)]
+struct Capped;
+
+impl PrizeRule for Capped {
+    fn copies(&self, card: &Card) -> usize {
+        card.count_matches()
+    }
+
+    fn capped(&self) -> bool {
+        true
+    }
+}
+
+// One copy of each of the next `points` cards, capped at the end of the deck
+#[derive(Debug, Clone, Copy)]
+struct PointsBased;
+
+impl PrizeRule for PointsBased {
+    fn copies(&self, card: &Card) -> usize {
+        card.points()
+    }
+
+    fn capped(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -103,3 +237,62 @@ impl Card {
             .count()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use crate::{Capped, Card, DeckError, PointsBased, Standard, tally, validate};
+
+    fn example() -> Vec<Card> {
+        include_str!("../example.txt").lines().map(|l| Card::parse(l).unwrap()).collect()
+    }
+
+    fn numbered(numbers: &[usize]) -> Vec<Card> {
+        numbers
+            .iter()
+            .map(|n| Card { number: *n, winning_numbers: BTreeSet::new(), picked_numbers: Vec::new() })
+            .collect()
+    }
+
+    #[test]
+    fn example_tally() {
+        let cards = example();
+        assert_eq!(cards.iter().map(|c| c.points()).sum::<usize>(), 13);
+        assert_eq!(tally(&cards, &Standard).unwrap(), 30);
+        assert_eq!(tally(&cards, &Capped).unwrap(), 30);
+    }
+
+    #[test]
+    fn prize_rules() {
+        // Card 1 has 4 matches but only one card follows it
+        let cards: Vec<_> = ["Card 1: 1 2 3 4 | 1 2 3 4", "Card 2: 1 | 2"]
+            .into_iter()
+            .map(|l| Card::parse(l).unwrap())
+            .collect();
+        assert!(tally(&cards, &Standard).is_err());
+        assert_eq!(tally(&cards, &Capped).unwrap(), 3);
+
+        // Card 1 is worth 8 points (copies of 2..=6, capped), cards 2 and 3 are worth 2 and card 4 is worth 1
+        let cards = example();
+        assert_eq!(tally(&cards, &PointsBased).unwrap(), 1 + 2 + 4 + 8 + 14 + 2);
+    }
+
+    #[test]
+    fn validation() {
+        let cases = [
+            (vec![1, 2, 3], Ok(())),
+            (vec![], Ok(())),
+            (vec![1, 2, 2], Err(DeckError::Duplicate(2))),
+            (vec![1, 3], Err(DeckError::Gap(2))),
+            (vec![2, 3], Err(DeckError::Gap(1))),
+            (vec![0, 1], Err(DeckError::Gap(0))),
+            (vec![2, 1, 3], Err(DeckError::OutOfOrder { position: 0, number: 2 })),
+            (vec![99_999_999_999_999_999], Err(DeckError::Gap(1))),
+            (vec![1, usize::MAX, usize::MAX], Err(DeckError::Duplicate(usize::MAX))),
+            (vec![1, 2, 5], Err(DeckError::Gap(3))),
+        ];
+        for (numbers, expected) in cases {
+            assert_eq!(validate(&numbered(&numbers)), expected, "{numbers:?}");
+        }
+    }
+}
@@ -1,20 +1,36 @@
-use std::cmp::{max, min};
-use std::collections::{BTreeMap};
-use std::{mem};
+use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
 use std::ops::Range;
 use std::path::Path;
 use std::str::FromStr;
 use sdk::*;
 use sdk::anyhow::anyhow;
+use itertools::Itertools;
+
+const SEED: &str = "seed";
+const LOCATION: &str = "location";
 
 fn main() -> Result<()> {
     init();
+    let args = Args::parse(std::env::args().skip(1))?;
     let almanac = Almanac::from_file("day05_if_you_give/input.txt")?;
     debug!("Parsed almanac: {almanac:?}");
+
+    if let Some((from, to)) = &args.convert {
+        let map = almanac.map_between(from, to)?;
+        info!("{from}-to-{to} map:\n{map}");
+        for value in &args.values {
+            info!("{from} {value} -> {to} {}", map.apply(*value));
+        }
+        return Ok(());
+    }
+
+    debug!("Composed seed to location map:\n{}", almanac.composed());
     let transformed = almanac.run_seeds();
     debug!("Final seed locations: {transformed:?}");
     let (closest_seed, closest_location) = transformed.iter()
-        .min_by_key(|(seed, location)| **location)
+        .min_by_key(|(_, location)| **location)
         .ok_or(anyhow!("No seeds mapped!"))?;
     info!("Closest location (seeds): {closest_location} (seed {closest_seed})");
 
@@ -29,9 +45,50 @@ fn main() -> Result<()> {
 
     info!("Closest location (ranges): {min_location}");
 
+    let reverse_location = almanac.closest_location_reverse()?
+        .ok_or(anyhow!("No location reachable from any seed range"))?;
+    info!("Closest location (reverse search): {reverse_location}");
+    if reverse_location != min_location {
+        warn!("Reverse search ({reverse_location}) disagrees with forward ranges ({min_location})");
+    }
+
     Ok(())
 }
 
+// Command line options:
+//   --convert soil-to-humidity   print the composed map between two categories instead of solving
+//   --values 81,14,57            values to convert with `--convert`
+#[derive(Debug, Clone, Default)]
+struct Args {
+    convert: Option<(String, String)>,
+    values: Vec<usize>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item=String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(anyhow!("Missing value for {arg}"));
+            match arg.as_str() {
+                "--convert" => {
+                    let convert = value()?;
+                    let (from, to) = convert.split_once("-to-")
+                        .ok_or(anyhow!("Unable to parse `{convert}`; expected `<from>-to-<to>`"))?;
+                    parsed.convert = Some((from.to_owned(), to.to_owned()));
+                }
+                "--values" => {
+                    parsed.values = value()?
+                        .split(',')
+                        .map(usize::from_str)
+                        .collect::<std::result::Result<_, _>>()?;
+                }
+                other => return Err(anyhow!("Unexpected argument `{other}`")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Transformation {
     from: String,
@@ -40,49 +97,37 @@ struct Transformation {
 }
 
 impl Transformation {
-    fn transform_single(&self, input: usize) -> usize {
+    // The transformation as a normalized map. Where source ranges overlap, the first one listed wins,
+    // as it would when checking each range in turn.
+    fn to_map(&self) -> PiecewiseMap {
+        let mut segments: Vec<TransformationRange> = Vec::new();
         for range in &self.ranges {
-            if let Some(result) = range.transform_single(input) {
-                return result;
+            let mut uncovered = vec![range.from.clone()];
+            for segment in &segments {
+                uncovered = uncovered.iter().flat_map(|r| r.subtract(&segment.from)).collect();
+            }
+            for part in uncovered {
+                let to_start = range.to_start + (part.start - range.from.start);
+                segments.push(TransformationRange { from: part, to_start });
             }
         }
-        input
+        PiecewiseMap::normalized(segments)
     }
 
-    fn transform_ranges(&self, input: &[Range<usize>]) -> Vec<(Range<usize>, Range<usize>)> {
-        // Keep track of the output for each input range
-        let mut input: Vec<(Range<usize>, Option<Range<usize>>)> = input.iter().map(|r| (r.clone(), None)).collect();
-        for range in &self.ranges {
-            // collect anything not part of this range to be added back to input
-            for (input_range, output) in mem::take(&mut input) {
-                // Don't try to re-map what's already been mapped
-                if output.is_some() {
-                    input.push((input_range, output));
-                    continue;
-                }
-
-                let transformed = range.transform_range(&input_range);
-                // Add new mappings back to the dataset
-                if let Some((mapped_input, mapped_output)) = transformed.transformed {
-                    input.push((mapped_input, Some(mapped_output)));
-                }
-
-                // Add anything left over back to be iterated over again
-                input.extend(transformed.excluded.into_iter().map(|r| (r, None)));
-            }
-        }
-        input
-            .into_iter()
-            .map(|(input, output)| {
-                // Anything that didn't get mapped is mapped 1:1 with input
-                let output = output.unwrap_or_else(|| input.clone());
-                (input, output)
-            })
-            .collect()
+    // The transformation taking `to` back to `from`. Fails if two inputs share an output.
+    #[allow(unused)]
+    fn invert(&self) -> Result<Transformation> {
+        let inverted = self.to_map().invert()
+            .map_err(|e| anyhow!("Unable to invert {}-to-{}: {e}", self.from, self.to))?;
+        Ok(Transformation {
+            from: self.to.clone(),
+            to: self.from.clone(),
+            ranges: inverted.segments,
+        })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransformationRange {
     from: Range<usize>,
     to_start: usize,
@@ -97,32 +142,137 @@ impl TransformationRange {
         }
     }
 
-    fn transform_range(&self, input: &Range<usize>) -> RangeTransformation {
-        let mut transformation = RangeTransformation::default();
-        if input.overlaps(&self.from) {
-            if input.start < self.from.start {
-                transformation.excluded.push(input.start..self.from.start);
+    // The same mapping, from output back to input
+    fn invert(&self) -> TransformationRange {
+        TransformationRange {
+            from: self.to_start..(self.to_start + self.from.len()),
+            to_start: self.from.start,
+        }
+    }
+
+    fn shift(&self) -> isize {
+        self.to_start as isize - self.from.start as isize
+    }
+
+    fn is_identity(&self) -> bool {
+        self.to_start == self.from.start
+    }
+}
+
+impl Display for TransformationRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let to_end = self.to_start + (self.from.end - self.from.start);
+        write!(f, "{:?} -> {:?} ({:+})", self.from, self.to_start..to_end, self.shift())
+    }
+}
+
+// A map over all of `usize` made of sorted, non-overlapping, non-empty segments. Anything not in a
+// segment maps to itself. Adjacent segments with the same shift are merged, and segments that map to
+// themselves are dropped, so equal maps have equal segment lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct PiecewiseMap {
+    segments: Vec<TransformationRange>,
+}
+
+impl PiecewiseMap {
+    // `segments` must not overlap
+    fn normalized(mut segments: Vec<TransformationRange>) -> Self {
+        segments.retain(|s| !s.from.is_empty() && !s.is_identity());
+        segments.sort_by_key(|s| s.from.start);
+        let mut normalized: Vec<TransformationRange> = Vec::with_capacity(segments.len());
+        for segment in segments {
+            match normalized.last_mut() {
+                Some(last) if last.from.end == segment.from.start && last.shift() == segment.shift() => {
+                    last.from.end = segment.from.end;
+                }
+                _ => normalized.push(segment),
+            }
+        }
+        PiecewiseMap { segments: normalized }
+    }
+
+    fn apply(&self, input: usize) -> usize {
+        let i = self.segments.partition_point(|s| s.from.end <= input);
+        self.segments
+            .get(i)
+            .and_then(|s| s.transform_single(input))
+            .unwrap_or(input)
+    }
+
+    // Where each part of `input` ends up, in input order. Parts are split wherever the map changes.
+    fn apply_range(&self, input: &Range<usize>) -> Vec<Range<usize>> {
+        self.pieces(input)
+            .into_iter()
+            .map(|(piece, to_start)| to_start..(to_start + piece.len()))
+            .collect()
+    }
+
+    // Splits `input` into consecutive pieces that each map by a single shift, with where each starts
+    fn pieces(&self, input: &Range<usize>) -> Vec<(Range<usize>, usize)> {
+        let mut pieces = Vec::new();
+        let mut position = input.start;
+        let mut i = self.segments.partition_point(|s| s.from.end <= position);
+        while position < input.end {
+            match self.segments.get(i) {
+                Some(segment) if segment.from.start <= position => {
+                    let end = min(segment.from.end, input.end);
+                    pieces.push((position..end, segment.to_start + (position - segment.from.start)));
+                    position = end;
+                    i += 1;
+                }
+                next => {
+                    let end = next.map(|s| min(s.from.start, input.end)).unwrap_or(input.end);
+                    pieces.push((position..end, position));
+                    position = end;
+                }
             }
-            if input.end > self.from.end {
-                transformation.excluded.push(self.from.end..input.end)
+        }
+        pieces
+    }
+
+    // Applies `self`, then `next`
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut segments = Vec::new();
+        for (piece, to_start) in self.pieces(&(0..usize::MAX)) {
+            let image = to_start..(to_start + piece.len());
+            for (next_piece, next_to_start) in next.pieces(&image) {
+                let from_start = piece.start + (next_piece.start - image.start);
+                segments.push(TransformationRange {
+                    from: from_start..(from_start + next_piece.len()),
+                    to_start: next_to_start,
+                });
             }
-            let input_start = max(input.start, self.from.start);
-            let input_end = min(input.end, self.from.end);
-            let output_start = self.to_start + (input_start - self.from.start);
-            let output_end = self.to_start + (input_end - self.from.start);
-            transformation.transformed = Some((input_start..input_end, output_start..output_end));
-        } else {
-            transformation.excluded.push(input.clone());
         }
+        PiecewiseMap::normalized(segments)
+    }
 
-        transformation
+    // The map taking outputs back to inputs, if no two inputs share an output
+    fn invert(&self) -> Result<PiecewiseMap> {
+        let mut inverted: Vec<TransformationRange> = self.pieces(&(0..usize::MAX))
+            .into_iter()
+            .map(|(from, to_start)| TransformationRange { from, to_start }.invert())
+            .collect();
+        inverted.sort_by_key(|s| s.from.start);
+        if let Some((a, b)) = inverted.iter().tuple_windows().find(|(a, b)| a.from.overlaps(&b.from)) {
+            return Err(anyhow!("Map is not invertible: {a} and {b} overlap"));
+        }
+        Ok(PiecewiseMap::normalized(inverted))
     }
 }
 
-#[derive(Debug, Clone, Default)]
-struct RangeTransformation {
-    transformed: Option<(Range<usize>, Range<usize>)>,
-    excluded: Vec<Range<usize>>,
+impl Display for PiecewiseMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, "identity");
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{segment}")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +282,10 @@ struct Almanac {
     // part 2
     seed_ranges: Vec<Range<usize>>,
     transformations: Vec<Transformation>,
+    // Index into `transformations` of the map from each category
+    categories: BTreeMap<String, usize>,
+    // Every map from seed to location composed into one
+    composed: PiecewiseMap,
 }
 
 impl Almanac {
@@ -156,7 +310,7 @@ impl Almanac {
             let seeds = seeds
                 .trim_start_matches("seeds: ")
                 .split(" ")
-                .map(|s| usize::from_str(s))
+                .map(usize::from_str)
                 .collect::<std::result::Result<Vec<_>, _>>()?;
             Ok(seeds)
         }
@@ -179,7 +333,7 @@ impl Almanac {
         fn parse_range(line: &str) -> Result<TransformationRange> {
             let numbers = line
                 .split(" ")
-                .map(|n| usize::from_str(n))
+                .map(usize::from_str)
                 .collect::<std::result::Result<Vec<_>, _>>()?;
             let (to, from, len) = match numbers.as_slice() {
                 &[to, from, len] => (to, from, len),
@@ -192,7 +346,7 @@ impl Almanac {
         }
 
         let mut lines = lines(file)?;
-        let seeds = lines.next().ok_or(anyhow!("Missing seeds line in input"))??;
+        let seeds = lines.next().ok_or(anyhow!("Missing seeds line in input"))?;
         let seed_ranges = parse_seed_ranges(&seeds)?;
         let seeds = parse_seeds(&seeds)?;
 
@@ -200,7 +354,6 @@ impl Almanac {
         let mut active = None;
 
         for line in lines {
-            let line = line?;
             if let Some((from, to)) = parse_header(&line) {
                 if let Some(active) = active.take() {
                     transformations.push(active);
@@ -214,52 +367,161 @@ impl Almanac {
         if let Some(active) = active {
             transformations.push(active)
         }
-        Ok(Almanac {
+        Almanac::new(seeds, seed_ranges, transformations)
+    }
+
+    // Checks that the maps form a chain from seed to location: each category is mapped from at most
+    // once, no map's source ranges overlap, and following the maps never loops
+    fn new(seeds: Vec<usize>, seed_ranges: Vec<Range<usize>>, transformations: Vec<Transformation>) -> Result<Self> {
+        let mut categories = BTreeMap::new();
+        for (i, transformation) in transformations.iter().enumerate() {
+            if categories.insert(transformation.from.clone(), i).is_some() {
+                return Err(anyhow!("More than one map from `{}`", transformation.from));
+            }
+
+            let mut sources: Vec<_> = transformation.ranges.iter().map(|r| &r.from).collect();
+            sources.sort_by_key(|r| r.start);
+            if let Some((a, b)) = sources.iter().tuple_windows().find(|(a, b)| a.overlaps(b)) {
+                return Err(anyhow!("{}-to-{} map has overlapping source ranges {a:?} and {b:?}", transformation.from, transformation.to));
+            }
+        }
+
+        let mut almanac = Almanac {
             seeds,
             seed_ranges,
             transformations,
-        })
+            categories,
+            composed: PiecewiseMap::default(),
+        };
+        for category in almanac.categories.keys() {
+            almanac.chain_from(category)?;
+        }
+        almanac.composed = almanac.map_between(SEED, LOCATION)?;
+        Ok(almanac)
     }
 
-    // part 1
-    fn run_seeds(&self) -> BTreeMap<usize, usize> {
-        let mut seed_to_end = BTreeMap::new();
-        for seed in &self.seeds {
-            let mut stage_index = *seed;
-            for transformation in &self.transformations {
-                stage_index = transformation.transform_single(stage_index);
+    // Every map from `from` until the chain ends. Errors if the chain loops back on itself.
+    fn chain_from(&self, from: &str) -> Result<Vec<&Transformation>> {
+        let mut chain = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut category = from;
+        while let Some(i) = self.categories.get(category) {
+            if !visited.insert(category) {
+                let path: Vec<_> = chain.iter().map(|t: &&Transformation| t.from.as_str()).collect();
+                return Err(anyhow!("Maps from `{from}` loop back to `{category}`: {}", path.join(" -> ")));
+            }
+            let transformation = &self.transformations[*i];
+            chain.push(transformation);
+            category = &transformation.to;
+        }
+        Ok(chain)
+    }
+
+    // The maps leading from category `from` to category `to`
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&Transformation>> {
+        let mut chain = self.chain_from(from)?;
+        if from == to {
+            return Ok(Vec::new());
+        }
+        match chain.iter().position(|t| t.to == to) {
+            Some(end) => {
+                chain.truncate(end + 1);
+                Ok(chain)
+            }
+            None => {
+                let reached = chain.last().map(|t| t.to.as_str()).unwrap_or(from);
+                Err(anyhow!("No path from `{from}` to `{to}`: nothing maps from `{reached}`"))
             }
-            seed_to_end.insert(*seed, stage_index);
         }
-        seed_to_end
+    }
+
+    // Every map between two categories composed into one
+    fn map_between(&self, from: &str, to: &str) -> Result<PiecewiseMap> {
+        let path = self.path(from, to)?;
+        Ok(path.iter().fold(PiecewiseMap::default(), |composed, t| composed.then(&t.to_map())))
+    }
+
+    // Seed to location
+    fn composed(&self) -> &PiecewiseMap {
+        &self.composed
+    }
+
+    // part 1
+    fn run_seeds(&self) -> BTreeMap<usize, usize> {
+        let composed = self.composed();
+        self.seeds
+            .iter()
+            .map(|seed| (*seed, composed.apply(*seed)))
+            .collect()
     }
 
     fn run_ranges(&self) -> Vec<Range<usize>> {
-        // Map of input (seed ranges) to current stage. After transformations, values will be final stage (locations)
-        let mut ranges = self.seed_ranges.clone();
-        for stage in &self.transformations {
-            let range_maps = mem::take(&mut ranges);
-            for (_, output) in stage.transform_ranges(&range_maps) {
-                ranges.push(output)
+        let composed = self.composed();
+        self.seed_ranges
+            .iter()
+            .flat_map(|range| composed.apply_range(range))
+            .collect()
+    }
+
+    // Seeds (as ranges) that end up somewhere in `locations`
+    #[allow(unused)]
+    fn seeds_for_locations(&self, locations: &Range<usize>) -> Result<Vec<Range<usize>>> {
+        Ok(self.composed().invert()?.apply_range(locations))
+    }
+
+    // Part 2 searched backwards: walks locations in increasing order, mapping each stretch back to
+    // its seeds, and stops at the first stretch that contains a seed from `seed_ranges`
+    fn closest_location_reverse(&self) -> Result<Option<usize>> {
+        let inverse = self.composed().invert()?;
+        for (locations, seed_start) in inverse.pieces(&(0..usize::MAX)) {
+            let seeds = seed_start..(seed_start + locations.len());
+            let closest = self.seed_ranges
+                .iter()
+                .filter(|r| r.overlaps(&seeds))
+                .map(|r| locations.start + (r.start.max(seeds.start) - seeds.start))
+                .min();
+            if closest.is_some() {
+                return Ok(closest);
             }
         }
-        ranges
+        Ok(None)
     }
 }
 
-trait RangeOverlap {
+trait RangeOverlap: Sized {
     fn overlaps(&self, other: &Self) -> bool;
+
+    // The parts of `self` not in `other`
+    fn subtract(&self, other: &Self) -> Vec<Self>;
 }
 
-impl<Idx: PartialOrd> RangeOverlap for Range<Idx> {
+impl<Idx: PartialOrd + Copy> RangeOverlap for Range<Idx> {
     fn overlaps(&self, other: &Self) -> bool {
         self.start < other.end && other.start < self.end
     }
+
+    fn subtract(&self, other: &Self) -> Vec<Self> {
+        if !self.overlaps(other) {
+            return vec![self.clone()];
+        }
+        let mut remaining = Vec::new();
+        if self.start < other.start {
+            remaining.push(self.start..other.start);
+        }
+        if other.end < self.end {
+            remaining.push(other.end..self.end);
+        }
+        remaining
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{RangeOverlap, TransformationRange};
+    use crate::{Almanac, PiecewiseMap, RangeOverlap, Transformation, TransformationRange};
+
+    fn segment(from: std::ops::Range<usize>, to_start: usize) -> TransformationRange {
+        TransformationRange { from, to_start }
+    }
 
     #[test]
     fn test_transformation_range() {
@@ -296,4 +558,148 @@ mod tests {
         assert!((0..1).overlaps(&(0..10)));
         assert!(!(10..20).overlaps(&(0..10)));
     }
+
+    #[test]
+    fn range_subtract() {
+        assert_eq!((0..10).subtract(&(3..5)), vec![0..3, 5..10]);
+        assert_eq!((0..10).subtract(&(0..5)), vec![5..10]);
+        assert_eq!((0..10).subtract(&(5..15)), vec![0..5]);
+        assert_eq!((0..10).subtract(&(10..15)), vec![0..10]);
+        assert_eq!((3..5).subtract(&(0..10)), vec![]);
+    }
+
+    #[test]
+    fn normalization() {
+        let transformation = Transformation {
+            from: "a".to_owned(),
+            to: "b".to_owned(),
+            ranges: vec![
+                segment(10..20, 110),
+                // Overlaps the first range, which wins
+                segment(15..25, 0),
+                // Same shift as the first range and adjacent to what's left of the second, so merged
+                segment(25..30, 125),
+                // Maps to itself
+                segment(40..50, 40),
+            ],
+        };
+        let map = transformation.to_map();
+        assert_eq!(map.segments, vec![segment(10..20, 110), segment(20..25, 5), segment(25..30, 125)]);
+        assert_eq!(map.apply(9), 9);
+        assert_eq!(map.apply(15), 115);
+        assert_eq!(map.apply(22), 7);
+        assert_eq!(map.apply(29), 129);
+        assert_eq!(map.apply(30), 30);
+        assert_eq!(map.apply_range(&(5..27)), vec![5..10, 110..120, 5..10, 125..127]);
+    }
+
+    #[test]
+    fn composition() {
+        let almanac = Almanac::from_file("example.txt").unwrap();
+        let composed = almanac.composed();
+        for seed in 0..120 {
+            let staged = almanac.transformations.iter().fold(seed, |x, t| t.to_map().apply(x));
+            assert_eq!(composed.apply(seed), staged, "seed {seed}");
+        }
+        assert_eq!(almanac.run_seeds().values().min(), Some(&35));
+        assert_eq!(almanac.run_ranges().iter().map(|r| r.start).min(), Some(46));
+    }
+
+    #[test]
+    fn inversion() {
+        let almanac = Almanac::from_file("example.txt").unwrap();
+        let composed = almanac.composed();
+        let inverted = composed.invert().unwrap();
+        assert_eq!(inverted.invert().unwrap(), *composed);
+        for seed in 0..120 {
+            assert_eq!(inverted.apply(composed.apply(seed)), seed);
+        }
+
+        // Both 0..10 and 10..20 map onto 10..20
+        let collapsing = PiecewiseMap::normalized(vec![segment(0..10, 10)]);
+        assert!(collapsing.invert().is_err());
+    }
+
+    #[test]
+    fn transformation_inversion() {
+        let range = segment(15..25, 35);
+        assert_eq!(range.invert(), segment(35..45, 15));
+        assert_eq!(range.invert().invert(), range);
+
+        let almanac = Almanac::from_file("example.txt").unwrap();
+        for transformation in &almanac.transformations {
+            let inverted = transformation.invert().unwrap();
+            assert_eq!((inverted.from.as_str(), inverted.to.as_str()), (transformation.to.as_str(), transformation.from.as_str()));
+            let (map, inverse) = (transformation.to_map(), inverted.to_map());
+            for x in 0..120 {
+                assert_eq!(inverse.apply(map.apply(x)), x);
+            }
+        }
+    }
+
+    #[test]
+    fn reverse_search() {
+        let almanac = Almanac::from_file("example.txt").unwrap();
+        assert_eq!(almanac.closest_location_reverse().unwrap(), Some(46));
+
+        // Every seed the reverse lookup finds for a location range lands in it going forward
+        let locations = 40..60;
+        for seeds in almanac.seeds_for_locations(&locations).unwrap() {
+            for seed in seeds {
+                assert!(locations.contains(&almanac.composed().apply(seed)), "seed {seed}");
+            }
+        }
+        let found: usize = almanac.seeds_for_locations(&locations).unwrap().iter().map(|r| r.len()).sum();
+        assert_eq!(found, locations.len());
+    }
+
+    fn map(from: &str, to: &str, ranges: Vec<TransformationRange>) -> Transformation {
+        Transformation { from: from.to_owned(), to: to.to_owned(), ranges }
+    }
+
+    #[test]
+    fn category_paths() {
+        let almanac = Almanac::from_file("example.txt").unwrap();
+        let path: Vec<_> = almanac.path("soil", "humidity").unwrap().iter().map(|t| t.from.as_str()).collect();
+        assert_eq!(path, vec!["soil", "fertilizer", "water", "light", "temperature"]);
+        assert!(almanac.path("soil", "soil").unwrap().is_empty());
+        assert!(almanac.path("humidity", "soil").is_err());
+        assert!(almanac.path("seed", "moon").is_err());
+
+        // Seed 79 is soil 81 and humidity 78
+        assert_eq!(almanac.map_between("soil", "humidity").unwrap().apply(81), 78);
+        assert_eq!(almanac.map_between("seed", "soil").unwrap().apply(79), 81);
+    }
+
+    #[test]
+    fn category_validation() {
+        let chain = || vec![
+            map("seed", "soil", vec![segment(0..10, 5)]),
+            map("soil", "location", vec![]),
+        ];
+        assert!(Almanac::new(vec![], vec![], chain()).is_ok());
+
+        // Maps listed out of order are applied in chain order
+        let mut reversed = chain();
+        reversed.reverse();
+        assert_eq!(Almanac::new(vec![], vec![], reversed).unwrap().composed().apply(0), 5);
+
+        let broken = vec![map("seed", "soil", vec![]), map("water", "location", vec![])];
+        assert!(Almanac::new(vec![], vec![], broken).is_err());
+
+        let mut cyclic = chain();
+        cyclic.push(map("water", "light", vec![]));
+        cyclic.push(map("light", "water", vec![]));
+        assert!(Almanac::new(vec![], vec![], cyclic).is_err());
+
+        let mut duplicated = chain();
+        duplicated.push(map("seed", "water", vec![]));
+        assert!(Almanac::new(vec![], vec![], duplicated).is_err());
+
+        let overlapping = vec![
+            map("seed", "soil", vec![segment(0..10, 5), segment(9..12, 50)]),
+            map("soil", "location", vec![]),
+        ];
+        assert!(Almanac::new(vec![], vec![], overlapping).is_err());
+    }
 }
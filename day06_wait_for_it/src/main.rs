@@ -5,45 +5,117 @@ use sdk::anyhow::anyhow;
 
 fn main() -> Result<()> {
     init();
+    let model = BoatModel::from_args(std::env::args().skip(1))?;
+    info!("Boat model: {model:?}");
     let input = std::fs::read_to_string("day06_wait_for_it/input.txt")?;
 
     // Part 1
     let races = Race::parse_races(&input)?;
     debug!("Races: {races:?}");
 
-    let winning_hold_times: Vec<_> = races.iter().map(|r| r.winning_hold_times()).collect();
+    let winning_hold_times: Vec<_> = races.iter().map(|r| r.winning_holds(&model)).collect();
     debug!("Winning hold times: {winning_hold_times:?}");
+    for race in &races {
+        report_optimum(race, &model);
+    }
 
     let winning_product = winning_hold_times.into_iter().fold(1, |product, next| {
-        product * next.map(|r| r.end - r.start).unwrap_or(0)
+        product * count_holds(&next)
     });
     info!("Winning combination product: {winning_product}");
 
     // Part 2
     let race = Race::parse_race(&input)?;
     debug!("Race: {race:?}");
-    let winning_hold_times = race.winning_hold_times();
+    let winning_hold_times = race.winning_holds(&model);
     debug!("Winning hold times: {winning_hold_times:?}");
-    let winning_hold_times_count = winning_hold_times.map(|r| r.end - r.start).unwrap_or(0);
+    report_optimum(&race, &model);
+    let winning_hold_times_count = count_holds(&winning_hold_times);
     info!("Winning hold times count: {winning_hold_times_count:?}");
 
     Ok(())
 }
 
+fn count_holds(holds: &[Range<u128>]) -> u128 {
+    holds.iter().map(|r| r.end - r.start).sum()
+}
+
+fn report_optimum(race: &Race, model: &BoatModel) {
+    let (hold, distance) = race.optimal_hold(model);
+    match race.margin(model) {
+        Some(margin) => debug!("{race:?}: best hold {hold} goes {distance}, beating the record by {margin}"),
+        None => debug!("{race:?}: best hold {hold} goes {distance}, which doesn't beat the record"),
+    }
+}
+
+// How fast the boat goes after charging. Each millisecond of charging adds `acceleration` to the
+// speed, up to `max_speed`; charging past `charge_cap` milliseconds uses up race time without adding
+// any speed. The puzzle's boat is `acceleration` 1 with no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BoatModel {
+    acceleration: u128,
+    max_speed: Option<u128>,
+    charge_cap: Option<u128>,
+}
+
+impl BoatModel {
+    const PUZZLE: BoatModel = BoatModel { acceleration: 1, max_speed: None, charge_cap: None };
+
+    // --acceleration 2 --max-speed 10 --charge-cap 5
+    fn from_args(mut args: impl Iterator<Item=String>) -> Result<Self> {
+        let mut model = BoatModel::PUZZLE;
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or(anyhow!("Missing value for {arg}"))?;
+            let value = u128::from_str(&value)?;
+            match arg.as_str() {
+                "--acceleration" => model.acceleration = value,
+                "--max-speed" => model.max_speed = Some(value),
+                "--charge-cap" => model.charge_cap = Some(value),
+                other => return Err(anyhow!("Unexpected argument `{other}`")),
+            }
+        }
+        Ok(model)
+    }
+
+    fn speed(&self, hold: u128) -> u128 {
+        let charge = self.charge_cap.map_or(hold, |cap| hold.min(cap));
+        let speed = self.acceleration.saturating_mul(charge);
+        self.max_speed.map_or(speed, |max| speed.min(max))
+    }
+
+    // Hold time from which the speed stops increasing, if it ever does
+    fn saturation(&self) -> Option<u128> {
+        let max_speed_reached = match (self.max_speed, self.acceleration) {
+            (Some(_), 0) => Some(0),
+            (Some(max), acceleration) => Some(max.div_ceil(acceleration)),
+            (None, _) => None,
+        };
+        match (max_speed_reached, self.charge_cap) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Saturates rather than overflowing
+    fn distance(&self, time: u128, hold: u128) -> u128 {
+        self.speed(hold).saturating_mul(time - hold)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Race {
-    time: usize,
-    min_distance: usize,
+    time: u128,
+    min_distance: u128,
 }
 
 impl Race {
     // Part 2
     fn parse_race(input: &str) -> Result<Race> {
-        fn parse_line(header: &str, line: &str) -> Result<usize> {
+        fn parse_line(header: &str, line: &str) -> Result<u128> {
             if !line.starts_with(header) {
                 return Err(anyhow!("Could not parse `{line}`; expected it to start with `{header}`"));
             }
-            let number: usize = line
+            let number: u128 = line
                 .trim_start_matches(header)
                 .split_whitespace()
                 .collect::<Vec<_>>()
@@ -64,14 +136,14 @@ impl Race {
 
     // Part 1
     fn parse_races(input: &str) -> Result<Vec<Race>> {
-        fn parse_line(header: &str, line: &str) -> Result<Vec<usize>> {
+        fn parse_line(header: &str, line: &str) -> Result<Vec<u128>> {
             if !line.starts_with(header) {
                 return Err(anyhow!("Could not parse `{line}`; expected it to start with `{header}`"));
             }
             let numbers = line
                 .trim_start_matches(header)
                 .split_whitespace()
-                .map(|n| usize::from_str(n))
+                .map(u128::from_str)
                 .collect::<std::result::Result<_, _>>()?;
             Ok(numbers)
         }
@@ -92,66 +164,235 @@ impl Race {
         Ok(races)
     }
 
-    fn winning_hold_times(&self) -> Option<Range<usize>> {
-        // min_distance < (time - hold_time) * hold_time = time * hold_time - hold_time.pow(2)
-        // hold_time.pow(2) - hold_time * time + min_distance < 0
-        solve_quadratic_range(1.0, -1.0 * self.time as f64, self.min_distance as f64)
+    // Hold times that beat the record: hold * (time - hold) > min_distance. The distance is symmetric
+    // around time / 2, so if `first` is the shortest winning hold, `time - first` is the longest.
+    fn winning_hold_times(&self) -> Option<Range<u128>> {
+        let first = self.first_winning_hold()?;
+        Some(first..(self.time - first + 1))
     }
-}
 
-// (-b±√(b²-4ac))/(2a)
-fn solve_quadratic(a: f64, b: f64, c: f64) -> (f64, f64) {
-    let first = ((-1.0 * b) + (b.powi(2) - (4.0 * a * c)).sqrt()) / (2.0 * a);
-    let second = ((-1.0 * b) - (b.powi(2) - (4.0 * a * c)).sqrt()) / (2.0 * a);
-    (first.min(second), first.max(second))
-}
+    fn beats_record(&self, hold: u128) -> bool {
+        // A distance too large for u128 certainly beats any u128 record
+        hold.checked_mul(self.time - hold).is_none_or(|distance| distance > self.min_distance)
+    }
 
-// Helper for finding the integer range which satisfies the quadratic
-fn solve_quadratic_range(a: f64, b: f64, c: f64) -> Option<Range<usize>> {
-    let (start, end) = solve_quadratic(a, b, c);
-    let start = next_gt_int(start);
-    let end = next_lt_int(end) + 1;
-    (start >= 0).then(|| (start as usize)..(end as usize))
-}
+    // The shortest winning hold, found from the quadratic's smaller root
+    // (time - √(time² - 4 * min_distance)) / 2 using an integer square root, then nudged onto the
+    // exact boundary since the root is rounded
+    fn first_winning_hold(&self) -> Option<u128> {
+        let half = self.time / 2;
+        if !self.beats_record(half) {
+            return None;
+        }
+        let estimate = match self.time.checked_mul(self.time) {
+            Some(square) => match self.min_distance.checked_mul(4) {
+                Some(min) if min < square => (self.time - (square - min).isqrt()) / 2,
+                // Only reachable if the record is unbeatable, which was ruled out above
+                _ => half,
+            },
+            // time² doesn't fit; binary search the (monotonic) first half instead
+            None => {
+                let (mut low, mut high) = (0, half);
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    if self.beats_record(mid) {
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+                low
+            }
+        };
+        let mut first = estimate.min(half);
+        while !self.beats_record(first) {
+            first += 1;
+        }
+        while first > 0 && self.beats_record(first - 1) {
+            first -= 1;
+        }
+        Some(first)
+    }
+
+    // Hold times that beat the record with `model`, as sorted, disjoint ranges. Until the speed stops
+    // increasing the distance is `acceleration` times the puzzle's quadratic, and after that it falls
+    // linearly, so each stretch is solved exactly on its own.
+    fn winning_holds(&self, model: &BoatModel) -> Vec<Range<u128>> {
+        // Only a saturation point within the race matters
+        let saturation = model.saturation().filter(|s| *s <= self.time);
+        let mut holds = Vec::new();
+
+        // Accelerating: acceleration * hold * (time - hold) > min_distance
+        if let Some(min_distance) = self.min_distance.checked_div(model.acceleration) {
+            let scaled = Race { time: self.time, min_distance };
+            if let Some(range) = scaled.winning_hold_times() {
+                let range = range.start..saturation.map_or(range.end, |s| range.end.min(s));
+                if !range.is_empty() {
+                    holds.push(range);
+                }
+            }
+        }
+
+        // Saturated: top_speed * (time - hold) > min_distance
+        if let Some(saturation) = saturation {
+            let top_speed = model.speed(saturation);
+            let last = self.min_distance
+                .checked_div(top_speed)
+                .and_then(|d| d.checked_add(1))
+                .and_then(|d| self.time.checked_sub(d));
+            if let Some(last) = last.filter(|last| *last >= saturation) {
+                holds.push(saturation..(last + 1));
+            }
+        }
 
-fn next_lt_int(f: f64) -> i64 {
-    if f.floor() == f {
-        (f as i64) - 1
-    } else {
-        f.floor() as i64
+        // Join ranges that meet at the saturation point
+        let mut merged: Vec<Range<u128>> = Vec::with_capacity(holds.len());
+        for range in holds {
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+        merged
     }
-}
 
-fn next_gt_int(f: f64) -> i64 {
-    if f.ceil() == f {
-        (f as i64) + 1
-    } else {
-        f.ceil() as i64
+    // The (earliest) hold that goes furthest with `model`, and how far it goes
+    fn optimal_hold(&self, model: &BoatModel) -> (u128, u128) {
+        // The quadratic peaks at time / 2; once saturated, holding longer only loses time
+        let saturation = model.saturation().filter(|s| *s <= self.time);
+        let last_accelerating = saturation.map_or(self.time, |s| s.saturating_sub(1));
+        let candidates = [self.time / 2, self.time.div_ceil(2)]
+            .map(|hold| hold.min(last_accelerating));
+        candidates
+            .into_iter()
+            .chain(saturation)
+            .map(|hold| (hold, model.distance(self.time, hold)))
+            .fold((0, model.distance(self.time, 0)), |best, next| {
+                if next.1 > best.1 || (next.1 == best.1 && next.0 < best.0) {
+                    next
+                } else {
+                    best
+                }
+            })
+    }
+
+    // How far past the record the optimal hold goes, if it beats it at all
+    fn margin(&self, model: &BoatModel) -> Option<u128> {
+        let (_, distance) = self.optimal_hold(model);
+        distance.checked_sub(self.min_distance).filter(|margin| *margin > 0)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{next_ge_int, next_lt_int, solve_quadratic_range};
+    use crate::{BoatModel, Race};
+
+    fn brute_force(race: Race) -> u128 {
+        (0..=race.time).filter(|hold| hold * (race.time - hold) > race.min_distance).count() as u128
+    }
+
+    fn count(race: Race) -> u128 {
+        race.winning_hold_times().map(|r| r.end - r.start).unwrap_or(0)
+    }
+
+    #[test]
+    fn example() {
+        let cases = [((7, 9), Some(2..6)), ((15, 40), Some(4..12)), ((30, 200), Some(11..20)), ((71530, 940200), Some(14..71517))];
+        for ((time, min_distance), expected) in cases {
+            assert_eq!(Race { time, min_distance }.winning_hold_times(), expected);
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for time in 0..150 {
+            for min_distance in 0..=(time * time / 4 + 1) {
+                let race = Race { time, min_distance };
+                assert_eq!(count(race), brute_force(race), "{race:?}");
+            }
+        }
+    }
 
     #[test]
-    fn test_quadratic() {
-        let cases = [((1.0, -7.0, 9.0), 2..6)];
-        for ((a, b, c), expected) in cases {
-            let range = solve_quadratic_range(a, b, c).unwrap();
-            assert_eq!(range, expected);
+    fn large_values() {
+        // Past 2^53, where f64 can no longer represent every integer
+        let time = (1 << 60) + 7;
+        for min_distance in [0, 1 << 53, (1 << 100) + 3, (time / 2) * (time - time / 2) - 1] {
+            assert_boundaries(Race { time, min_distance });
         }
+        assert_eq!(Race { time, min_distance: (time / 2) * (time - time / 2) }.winning_hold_times(), None);
+
+        // time² overflows u128
+        for time in [1 << 70, u128::MAX, u128::MAX - 1] {
+            for min_distance in [0, 1, 1 << 100, u128::MAX - 1] {
+                assert_boundaries(Race { time, min_distance });
+            }
+            assert!(Race { time, min_distance: u128::MAX }.winning_hold_times().is_some());
+        }
+
+        // The boat model's holds and optimum at the very largest time
+        let race = Race { time: u128::MAX, min_distance: 1 << 100 };
+        assert_eq!(race.winning_holds(&BoatModel::PUZZLE), vec![race.winning_hold_times().unwrap()]);
+        assert_eq!(race.optimal_hold(&BoatModel::PUZZLE).0, u128::MAX / 2);
+        // Top speed from the first hold, so every hold after it travels time - hold
+        let capped = BoatModel { acceleration: 1, max_speed: Some(1), charge_cap: None };
+        assert_eq!(race.winning_holds(&capped), vec![1..(u128::MAX - (1 << 100))]);
+        assert_eq!(race.optimal_hold(&capped), (1, u128::MAX - 1));
+        assert_eq!(race.margin(&capped), Some(u128::MAX - 1 - (1 << 100)));
+        assert_eq!(Race { time: u128::MAX, min_distance: u128::MAX }.winning_holds(&capped), vec![]);
+    }
+
+    // Checks the holds either side of each end of the winning range
+    fn assert_boundaries(race: Race) {
+        let range = race.winning_hold_times().unwrap();
+        assert!(race.beats_record(range.start), "{race:?}");
+        assert!(race.beats_record(range.end - 1), "{race:?}");
+        assert!(range.start == 0 || !race.beats_record(range.start - 1), "{race:?}");
+        assert!(range.end > race.time || !race.beats_record(range.end), "{race:?}");
+    }
+
+    fn brute_force_holds(race: Race, model: &BoatModel) -> Vec<u128> {
+        (0..=race.time).filter(|hold| model.distance(race.time, *hold) > race.min_distance).collect()
     }
 
     #[test]
-    fn next_ints() {
-        let cases = [
-            (1.1, 1, 2),
-            (1.0, 0, 1),
-        ];
-        for (f, lt, ge) in cases {
-            assert_eq!(next_lt_int(f), lt);
-            assert_eq!(next_ge_int(f), ge);
+    fn boat_models() {
+        let mut models = vec![BoatModel::PUZZLE];
+        for acceleration in 0..4 {
+            for max_speed in [None, Some(0), Some(1), Some(5), Some(12)] {
+                for charge_cap in [None, Some(0), Some(3), Some(8)] {
+                    models.push(BoatModel { acceleration, max_speed, charge_cap });
+                }
+            }
+        }
+        for model in &models {
+            for time in 0..40 {
+                for min_distance in (0..120).step_by(7) {
+                    let race = Race { time, min_distance };
+                    let holds: Vec<_> = race.winning_holds(model).into_iter().flatten().collect();
+                    assert_eq!(holds, brute_force_holds(race, model), "{race:?} {model:?}");
+
+                    let best = (0..=time).map(|hold| model.distance(time, hold)).max().unwrap();
+                    let (hold, distance) = race.optimal_hold(model);
+                    assert_eq!(distance, best, "{race:?} {model:?}");
+                    assert_eq!(model.distance(time, hold), best, "{race:?} {model:?}");
+                    assert_eq!(race.margin(model), best.checked_sub(min_distance).filter(|m| *m > 0));
+                }
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn puzzle_model() {
+        let race = Race { time: 30, min_distance: 200 };
+        assert_eq!(race.winning_holds(&BoatModel::PUZZLE), vec![11..20]);
+        assert_eq!(race.optimal_hold(&BoatModel::PUZZLE), (15, 225));
+        assert_eq!(race.margin(&BoatModel::PUZZLE), Some(25));
+
+        // Capped at speed 10, the best is to stop charging as soon as that's reached
+        let capped = BoatModel { acceleration: 1, max_speed: Some(10), charge_cap: None };
+        assert_eq!(race.winning_holds(&capped), vec![]);
+        assert_eq!(race.optimal_hold(&capped), (10, 200));
+        assert_eq!(race.margin(&capped), None);
+    }
+}
@@ -1,41 +1,45 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use sdk::*;
 use sdk::anyhow::anyhow;
 
-const JOKERS_WILD: bool = true;
-
 fn main() -> Result<()> {
     init();
-    let mut hands = Vec::new();
-    for line in lines("day07_camel_cards/input.txt")? {
-        let line = line?;
-        hands.push(parse_line(&line)?);
+    let mut rule_sets = vec![RuleSet::standard(), RuleSet::jokers_wild()];
+    if let Some(custom) = RuleSet::from_args(std::env::args().skip(1))? {
+        rule_sets.push(custom);
     }
 
-    debug!("Hands: {hands:?}");
+    let lines: Vec<_> = lines("day07_camel_cards/input.txt")?.collect();
+    for rules in &rule_sets {
+        let hands = lines
+            .iter()
+            .map(|line| parse_line(line, rules))
+            .collect::<Result<Vec<_>>>()?;
 
-    let hands = rank(&hands);
+        debug!("Hands ({}): {hands:?}", rules.name);
 
-    for RankedHand { hand, bid, rank } in &hands {
-        debug!("Hand {hand} with bid {bid} is ranked {rank} ({:?})", hand.type_());
-    }
+        let (hands, report) = rank(&hands);
+        debug!("Ranking ({}):\n{report}", rules.name);
+        info!("Hand types ({}): {:?}", rules.name, report.type_counts);
 
-    let winnings: usize = hands.iter()
-        .map(|h| h.bid * h.rank)
-        .sum();
+        let winnings: usize = hands.iter()
+            .map(|h| h.bid * h.rank)
+            .sum();
 
-    info!("Winnings: {winnings}");
+        info!("Winnings ({}): {winnings}", rules.name);
+    }
     Ok(())
 }
 
-fn parse_line(line: &str) -> Result<(Hand, usize)> {
+fn parse_line(line: &str, rules: &RuleSet) -> Result<(Hand, usize)> {
     let (hand, bid) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
         &[hand, bid] => (hand, bid),
         _ => return Err(anyhow!("Unable to parse `{line}`: cannot split into hand and bid")),
     };
-    let hand = Hand::parse(hand)?;
+    let hand = Hand::parse(hand, rules)?;
     let bid = usize::from_str(bid)?;
     Ok((hand, bid))
 }
@@ -47,60 +51,230 @@ struct RankedHand {
     rank: usize,
 }
 
-fn rank(hands: &[(Hand, usize)]) -> Vec<RankedHand> {
+fn rank(hands: &[(Hand, usize)]) -> (Vec<RankedHand>, RankingReport) {
     let mut hands = hands.to_vec();
     hands.sort();
-    hands.into_iter()
+    let hands: Vec<_> = hands.into_iter()
         .enumerate()
         .map(|(i, (hand, bid))| RankedHand {
             hand,
             bid,
             rank: i + 1,
         })
-        .collect()
+        .collect();
+    let report = RankingReport::new(&hands);
+    (hands, report)
 }
 
+// What put one hand below the next in the ranking
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Card(char);
+enum Decider {
+    // The lower hand has a weaker type
+    Type(HandType, HandType),
+    // Same type; the cards first differ at this position (from 0)
+    Card { position: usize, lower: Card, higher: Card },
+    // Identical hands, which are ordered by bid
+    Bid,
+}
+
+impl Decider {
+    fn between(lower: &Hand, higher: &Hand) -> Self {
+        if lower.type_ != higher.type_ {
+            return Decider::Type(lower.type_, higher.type_);
+        }
+        lower.cards
+            .iter()
+            .zip(&higher.cards)
+            .enumerate()
+            .find(|(_, (a, b))| a.cmp(b) != Ordering::Equal)
+            .map(|(position, (lower, higher))| Decider::Card { position, lower: *lower, higher: *higher })
+            .unwrap_or(Decider::Bid)
+    }
+}
 
-impl Card {
-    const CARDINALITY: usize = 14;
+impl Display for Decider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Decider::Type(lower, higher) => write!(f, "type ({lower:?} < {higher:?})"),
+            Decider::Card { position, lower, higher } => write!(f, "card {} ({lower} < {higher})", position + 1),
+            Decider::Bid => write!(f, "identical hands, ordered by bid"),
+        }
+    }
+}
 
-    fn rank(&self) -> usize {
-        match self.0 {
-            'A' => 14,
-            'K' => 13,
-            'Q' => 12,
-            'J' => 11,
-            'T' => 10,
-            'X' => 1,
-            other if other.is_numeric() => {
-                other.to_digit(10).unwrap() as usize
+// Why the ranking came out the way it did
+#[derive(Debug, Clone)]
+struct RankingReport {
+    // For each hand but the last: its rank, and what put it below the next hand
+    deciders: Vec<(usize, Decider)>,
+    type_counts: BTreeMap<HandType, usize>,
+    // Each hand by rank, for display
+    hands: Vec<(Hand, usize)>,
+}
+
+impl RankingReport {
+    fn new(hands: &[RankedHand]) -> Self {
+        let deciders = hands
+            .windows(2)
+            .map(|pair| (pair[0].rank, Decider::between(&pair[0].hand, &pair[1].hand)))
+            .collect();
+        let mut type_counts = BTreeMap::new();
+        for hand in hands {
+            *type_counts.entry(hand.hand.type_()).or_default() += 1;
+        }
+        let hands = hands.iter().map(|h| (h.hand.clone(), h.bid)).collect();
+        RankingReport { deciders, type_counts, hands }
+    }
+}
+
+impl Display for RankingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (hand, bid)) in self.hands.iter().enumerate() {
+            write!(f, "#{} {hand} ({:?}, bid {bid})", i + 1, hand.type_())?;
+            if let Some((_, decider)) = self.deciders.get(i) {
+                write!(f, " < #{}: {decider}", i + 2)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "Hand types:")?;
+        for (type_, count) in &self.type_counts {
+            writeln!(f, "    {type_:?}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+// Everything about how hands are read and compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RuleSet {
+    name: String,
+    // Card labels, weakest first
+    order: Vec<char>,
+    // The card that stands in for whichever card makes the strongest hand type, if any
+    wild: Option<char>,
+    hand_size: usize,
+    // Whether runs of consecutive cards (in `order`) count as straights
+    straights: bool,
+    // Suit symbols. When there are any, each card is written as its label then its suit (`Ah`), and
+    // cards all of one suit count as flushes.
+    suits: Vec<char>,
+}
+
+impl RuleSet {
+    // Part 1
+    fn standard() -> Self {
+        RuleSet::new("standard", "23456789TJQKA", None, 5).expect("standard rules are valid")
+    }
+
+    // Part 2: jokers are wild, but weakest when breaking ties
+    fn jokers_wild() -> Self {
+        RuleSet::new("jokers wild", "J23456789TQKA", Some('J'), 5).expect("jokers wild rules are valid")
+    }
+
+    fn new(name: &str, order: &str, wild: Option<char>, hand_size: usize) -> Result<Self> {
+        let order: Vec<char> = order.chars().collect();
+        if let Some(duplicate) = order.iter().find(|c| order.iter().filter(|o| o == c).count() > 1) {
+            return Err(anyhow!("Card `{duplicate}` appears more than once in the card order"));
+        }
+        if let Some(wild) = wild.filter(|w| !order.contains(w)) {
+            return Err(anyhow!("Wild card `{wild}` is not in the card order"));
+        }
+        if hand_size == 0 {
+            return Err(anyhow!("Hands must have at least one card"));
+        }
+        Ok(RuleSet { name: name.to_owned(), order, wild, hand_size, straights: false, suits: Vec::new() })
+    }
+
+    fn with_straights(mut self) -> Self {
+        self.straights = true;
+        self
+    }
+
+    fn with_suits(mut self, suits: &str) -> Result<Self> {
+        let suits: Vec<char> = suits.chars().collect();
+        if let Some(duplicate) = suits.iter().find(|c| suits.iter().filter(|s| s == c).count() > 1) {
+            return Err(anyhow!("Suit `{duplicate}` appears more than once"));
+        }
+        self.suits = suits;
+        Ok(self)
+    }
+
+    // A custom rule set to run alongside parts 1 and 2, if any options are given:
+    //   --order 23456789TJQKA   card labels, weakest first (defaults to part 1's)
+    //   --wild J                the wild card (defaults to none)
+    //   --hand-size 5           cards per hand (defaults to 5)
+    //   --straights             count straights
+    //   --suits hdcs            cards have suits, and flushes count
+    fn from_args(mut args: impl Iterator<Item=String>) -> Result<Option<Self>> {
+        let standard = RuleSet::standard();
+        let mut order: String = standard.order.iter().collect();
+        let mut wild = None;
+        let mut hand_size = standard.hand_size;
+        let mut straights = false;
+        let mut suits = String::new();
+        let mut custom = false;
+        while let Some(arg) = args.next() {
+            custom = true;
+            if arg == "--straights" {
+                straights = true;
+                continue;
+            }
+            let value = args.next().ok_or(anyhow!("Missing value for {arg}"))?;
+            match arg.as_str() {
+                "--order" => order = value,
+                "--wild" => {
+                    let mut chars = value.chars();
+                    wild = match (chars.next(), chars.next()) {
+                        (Some(c), None) => Some(c),
+                        _ => return Err(anyhow!("Wild card must be a single character, found `{value}`")),
+                    };
+                }
+                "--hand-size" => hand_size = usize::from_str(&value)?,
+                "--suits" => suits = value,
+                other => return Err(anyhow!("Unexpected argument `{other}`")),
             }
-            _ => panic!("Illegal card")
         }
+        if !custom {
+            return Ok(None);
+        }
+        let mut rules = RuleSet::new("custom", &order, wild, hand_size)?.with_suits(&suits)?;
+        if straights {
+            rules = rules.with_straights();
+        }
+        Ok(Some(rules))
     }
 
-    // Card index for use in arrays. Jokers ('X') are 0, '2's are 1, and going up from there
-    fn index(&self) -> usize {
-        self.rank() - 1
+    fn card(&self, label: char, suit: Option<char>) -> Result<Card> {
+        let rank = self.order
+            .iter()
+            .position(|c| *c == label)
+            .ok_or(anyhow!("Illegal card `{label}` for {} rules", self.name))?;
+        if let Some(suit) = suit.filter(|s| !self.suits.contains(s)) {
+            return Err(anyhow!("Illegal suit `{suit}` for {} rules", self.name));
+        }
+        Ok(Card { label, suit, rank, wild: self.wild == Some(label) })
     }
 }
 
-impl TryFrom<char> for Card {
-    type Error = anyhow::Error;
+#[derive(Debug, Clone, Copy)]
+struct Card {
+    label: char,
+    suit: Option<char>,
+    // Position in the rule set's card order
+    rank: usize,
+    wild: bool,
+}
 
-    fn try_from(value: char) -> Result<Self> {
-        let char = match value {
-            '0'..='9' | 'T' | 'Q' | 'K' | 'A' => value,
-            'J' if JOKERS_WILD => 'X',
-            'J' => value,
-            _ => return Err(anyhow!("Illegal card `{value}`")),
-        };
-        Ok(Card(char))
+// Cards are equal when they rank the same, matching `Ord`: suits never break ties, so `2h` and `2s`
+// are the same card as far as ordering hands goes
+impl PartialEq for Card {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank == other.rank
     }
 }
 
+impl Eq for Card {}
+
 impl PartialOrd for Card {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -109,76 +283,54 @@ impl PartialOrd for Card {
 
 impl Ord for Card {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.rank().cmp(&other.rank())
+        self.rank.cmp(&other.rank)
     }
 }
 
 impl Display for Card {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.label)?;
+        if let Some(suit) = self.suit {
+            write!(f, "{suit}")?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-struct Hand([Card; 5]);
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Hand {
+    cards: Vec<Card>,
+    // Worked out once when parsing, since sorting compares hands many times
+    type_: HandType,
+}
 
 impl Hand {
-    fn parse(input: &str) -> Result<Self> {
-        if input.len() != 5 {
-            return Err(anyhow!("Illegal hand: {input} - expected 5 characters"));
-        }
-        let mut cards = [Card('2'); 5];
-        for (i, card) in input.chars().enumerate() {
-            cards[i] = card.try_into()?;
+    fn parse(input: &str, rules: &RuleSet) -> Result<Self> {
+        let chars: Vec<char> = input.chars().collect();
+        let cards = if rules.suits.is_empty() {
+            chars.iter().map(|c| rules.card(*c, None)).collect::<Result<Vec<_>>>()?
+        } else {
+            if !chars.len().is_multiple_of(2) {
+                return Err(anyhow!("Illegal hand: {input} - expected each card to have a suit"));
+            }
+            chars.chunks(2).map(|c| rules.card(c[0], Some(c[1]))).collect::<Result<Vec<_>>>()?
+        };
+        if cards.len() != rules.hand_size {
+            return Err(anyhow!("Illegal hand: {input} - expected {} cards", rules.hand_size));
         }
-        Ok(Hand(cards))
+        let type_ = HandType::classify(&cards, rules);
+        Ok(Hand { cards, type_ })
     }
 
     fn type_(&self) -> HandType {
-        use HandType::*;
-
-        let mut counts = [0_usize; Card::CARDINALITY];
-        for card in &self.0 {
-            counts[card.index()] += 1;
-        }
-
-        trace!("{self} counts: {counts:?}");
-
-        let num_jokers = counts[0];
-        let max_count = counts[1..].iter().cloned().max().unwrap_or(0);
-
-        let type_ = if max_count == 5 {
-            FiveOfAKind
-        } else if max_count == 4 {
-            FourOfAKind
-        } else if max_count == 3 {
-            if counts.iter().any(|&c| c == 2) {
-                FullHouse
-            } else {
-                ThreeOfAKind
-            }
-        } else if max_count == 2 {
-            if counts.iter().filter(|&&c| c == 2).count() == 2 {
-                TwoPair
-            } else {
-                OnePair
-            }
-        } else {
-            HighCard
-        };
-
-        trace!("{self} type before jokers: {type_:?}");
-
-        let type_ = type_.with_jokers(num_jokers);
-        trace!("{self} type after jokers: {type_:?}");
-        type_
+        self.type_
     }
 }
 
 impl Display for Hand {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Hand([")?;
-        for card in &self.0 {
+        for card in &self.cards {
             write!(f, "{card}")?;
         }
         write!(f, "])")?;
@@ -188,8 +340,8 @@ impl Display for Hand {
 
 impl Ord for Hand {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.type_().cmp(&other.type_()) {
-            Ordering::Equal => self.0.cmp(&other.0),
+        match self.type_.cmp(&other.type_) {
+            Ordering::Equal => self.cards.cmp(&other.cards),
             non_equal => non_equal,
         }
     }
@@ -208,36 +360,92 @@ enum HandType {
     OnePair,
     TwoPair,
     ThreeOfAKind,
+    Straight,
+    Flush,
     FullHouse,
     FourOfAKind,
+    StraightFlush,
     FiveOfAKind,
 }
 
 impl HandType {
-    fn with_jokers(self, num_jokers: usize) -> Self {
+    // The strongest type the cards can make, with wild cards standing in for whatever helps most
+    fn classify(cards: &[Card], rules: &RuleSet) -> Self {
         use HandType::*;
-        let mut type_ = self;
-        for _ in 0..num_jokers {
-            type_ = match type_ {
-                HighCard => OnePair,
-                OnePair => ThreeOfAKind,
-                TwoPair => FullHouse,
-                ThreeOfAKind | FullHouse => FourOfAKind,
-                FourOfAKind | FiveOfAKind => FiveOfAKind,
-            }
-        }
+
+        let type_ = Self::from_signature(&Self::signature(cards));
+        let straight = rules.straights && Self::is_straight(cards, rules.hand_size);
+        let flush = !rules.suits.is_empty() && Self::is_flush(cards);
+        let type_ = match (straight, flush) {
+            (true, true) => type_.max(StraightFlush),
+            (true, false) => type_.max(Straight),
+            (false, true) => type_.max(Flush),
+            (false, false) => type_,
+        };
+        trace!("{cards:?}: {type_:?}");
         type_
     }
 
+    // How many of each card there are, largest group first. Wild cards always do best joining the
+    // largest group.
+    fn signature(cards: &[Card]) -> Vec<usize> {
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for card in cards.iter().filter(|c| !c.wild) {
+            *counts.entry(card.rank).or_default() += 1;
+        }
+        let mut signature: Vec<_> = counts.into_values().collect();
+        signature.sort_by(|a, b| b.cmp(a));
+
+        let num_wild = cards.iter().filter(|c| c.wild).count();
+        match signature.first_mut() {
+            Some(largest) => *largest += num_wild,
+            None => signature.push(num_wild),
+        }
+        signature
+    }
+
+    fn from_signature(signature: &[usize]) -> Self {
+        use HandType::*;
+        match signature {
+            [n, ..] if *n >= 5 => FiveOfAKind,
+            [4, ..] => FourOfAKind,
+            [3, n, ..] if *n >= 2 => FullHouse,
+            [3, ..] => ThreeOfAKind,
+            [2, 2, ..] => TwoPair,
+            [2, ..] => OnePair,
+            _ => HighCard,
+        }
+    }
+
+    // Every card different and, with wild cards filling any gaps, consecutive
+    fn is_straight(cards: &[Card], hand_size: usize) -> bool {
+        let ranks: Vec<_> = cards.iter().filter(|c| !c.wild).map(|c| c.rank).collect();
+        let distinct = ranks.iter().all(|r| ranks.iter().filter(|o| *o == r).count() == 1);
+        let span = ranks.iter().max().zip(ranks.iter().min()).map_or(0, |(max, min)| max - min + 1);
+        hand_size > 1 && distinct && span <= hand_size
+    }
+
+    // Every card (other than wild cards) the same suit
+    fn is_flush(cards: &[Card]) -> bool {
+        let mut suits = cards.iter().filter(|c| !c.wild).map(|c| c.suit);
+        match suits.next() {
+            Some(first) => suits.all(|s| s == first),
+            None => true,
+        }
+    }
+
     fn rank(&self) -> usize {
         match self {
             HandType::HighCard => 0,
             HandType::OnePair => 1,
             HandType::TwoPair => 2,
             HandType::ThreeOfAKind => 3,
-            HandType::FullHouse => 4,
-            HandType::FourOfAKind => 5,
-            HandType::FiveOfAKind => 6,
+            HandType::Straight => 4,
+            HandType::Flush => 5,
+            HandType::FullHouse => 6,
+            HandType::FourOfAKind => 7,
+            HandType::StraightFlush => 8,
+            HandType::FiveOfAKind => 9,
         }
     }
 }
@@ -253,3 +461,133 @@ impl Ord for HandType {
         self.rank().cmp(&other.rank())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+    use crate::{Decider, HandType, Hand, parse_line, rank, RuleSet};
+
+    fn winnings(input: &str, rules: &RuleSet) -> usize {
+        let hands: Vec<_> = input.lines().map(|l| parse_line(l, rules).unwrap()).collect();
+        rank(&hands).0.iter().map(|h| h.bid * h.rank).sum()
+    }
+
+    #[test]
+    fn example() {
+        let input = include_str!("../example.txt");
+        assert_eq!(winnings(input, &RuleSet::standard()), 6440);
+        assert_eq!(winnings(input, &RuleSet::jokers_wild()), 5905);
+    }
+
+    #[test]
+    fn rule_sets() {
+        let standard = RuleSet::standard();
+        let jokers = RuleSet::jokers_wild();
+        assert_eq!(Hand::parse("KTJJT", &standard).unwrap().type_(), HandType::TwoPair);
+        assert_eq!(Hand::parse("KTJJT", &jokers).unwrap().type_(), HandType::FourOfAKind);
+        assert!(Hand::parse("JKKK2", &jokers).unwrap() < Hand::parse("QQQQ2", &jokers).unwrap());
+        assert!(Hand::parse("JJJJJ", &jokers).unwrap() < Hand::parse("22222", &jokers).unwrap());
+        assert!(Hand::parse("JJJJJ", &standard).unwrap() > Hand::parse("22222", &standard).unwrap());
+
+        // Illegal cards and sizes are errors rather than panics
+        assert!(Hand::parse("KTXJT", &standard).is_err());
+        assert!(Hand::parse("KTJT", &standard).is_err());
+
+        let small = RuleSet::new("small", "A23", Some('A'), 3).unwrap();
+        assert_eq!(Hand::parse("A23", &small).unwrap().type_(), HandType::OnePair);
+        assert_eq!(Hand::parse("A22", &small).unwrap().type_(), HandType::ThreeOfAKind);
+        assert!(Hand::parse("A22", &small).unwrap() < Hand::parse("222", &small).unwrap());
+
+        assert!(RuleSet::new("bad", "23456789TJQKA", Some('X'), 5).is_err());
+        assert!(RuleSet::new("bad", "2234", None, 5).is_err());
+        assert!(RuleSet::new("bad", "234", None, 0).is_err());
+    }
+
+    #[test]
+    fn signatures() {
+        let jokers = RuleSet::jokers_wild();
+        let cases = [
+            ("23456", vec![1, 1, 1, 1, 1], HandType::HighCard),
+            ("2345J", vec![2, 1, 1, 1], HandType::OnePair),
+            ("22334", vec![2, 2, 1], HandType::TwoPair),
+            ("2233J", vec![3, 2], HandType::FullHouse),
+            ("223JJ", vec![4, 1], HandType::FourOfAKind),
+            ("JJJJJ", vec![5], HandType::FiveOfAKind),
+            ("2JJJJ", vec![5], HandType::FiveOfAKind),
+        ];
+        for (hand, signature, type_) in cases {
+            let hand = Hand::parse(hand, &jokers).unwrap();
+            assert_eq!(HandType::signature(&hand.cards), signature, "{hand}");
+            assert_eq!(hand.type_(), type_, "{hand}");
+        }
+    }
+
+    #[test]
+    fn straights_and_flushes() {
+        let straights = RuleSet::new("straights", "J23456789TQKA", Some('J'), 5).unwrap().with_straights();
+        let cases = [
+            ("23456", HandType::Straight),
+            ("6J452", HandType::Straight),
+            ("2345K", HandType::HighCard),
+            ("22345", HandType::OnePair),
+            ("3333J", HandType::FiveOfAKind),
+        ];
+        for (hand, type_) in cases {
+            assert_eq!(Hand::parse(hand, &straights).unwrap().type_(), type_, "{hand}");
+        }
+
+        let suited = RuleSet::standard().with_straights().with_suits("hdcs").unwrap();
+        let cases = [
+            ("2h3h4h5h6h", HandType::StraightFlush),
+            ("2h3h4h5h6s", HandType::Straight),
+            ("2h3h4h5hKh", HandType::Flush),
+            ("2h2s2d5h5s", HandType::FullHouse),
+        ];
+        for (hand, type_) in cases {
+            assert_eq!(Hand::parse(hand, &suited).unwrap().type_(), type_, "{hand}");
+        }
+        // Suits don't break ties, and equality agrees with the ordering
+        let a = Hand::parse("2h3h4h5h7s", &suited).unwrap();
+        let b = Hand::parse("2s3s4s5s7h", &suited).unwrap();
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_eq!(a, b);
+        assert!(Hand::parse("2h3h4h5h6", &suited).is_err());
+        assert!(Hand::parse("2h3h4h5h6x", &suited).is_err());
+    }
+
+    #[test]
+    fn report() {
+        let rules = RuleSet::standard();
+        let hands: Vec<_> = include_str!("../example.txt").lines().map(|l| parse_line(l, &rules).unwrap()).collect();
+        let (ranked, report) = rank(&hands);
+        let deciders: Vec<_> = report.deciders.iter().map(|(rank, decider)| (*rank, decider.to_string())).collect();
+        // 32T3K, KTJJT, KK677, T55J5, QQQJA
+        assert_eq!(deciders, vec![
+            (1, "type (OnePair < TwoPair)".to_owned()),
+            (2, "card 2 (T < K)".to_owned()),
+            (3, "type (TwoPair < ThreeOfAKind)".to_owned()),
+            (4, "card 1 (T < Q)".to_owned()),
+        ]);
+        assert_eq!(report.type_counts, BTreeMap::from([
+            (HandType::OnePair, 1),
+            (HandType::TwoPair, 2),
+            (HandType::ThreeOfAKind, 2),
+        ]));
+        assert_eq!(report.to_string().lines().next(), Some("#1 Hand([32T3K]) (OnePair, bid 765) < #2: type (OnePair < TwoPair)"));
+        assert_eq!(ranked.len(), report.deciders.len() + 1);
+
+        let twins = [(Hand::parse("AAAAA", &rules).unwrap(), 2), (Hand::parse("AAAAA", &rules).unwrap(), 1)];
+        assert_eq!(rank(&twins).1.deciders, vec![(1, Decider::Bid)]);
+
+        // Suits don't decide anything, so these come down to bid too
+        let suited = RuleSet::standard().with_suits("hdcs").unwrap();
+        let hands = [
+            (Hand::parse("2s3s4s5s7h", &suited).unwrap(), 2),
+            (Hand::parse("2h3h4h5h7s", &suited).unwrap(), 1),
+        ];
+        let (ranked, report) = rank(&hands);
+        assert_eq!(report.deciders, vec![(1, Decider::Bid)]);
+        assert_eq!(ranked[0].bid, 1);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use sdk::*;
+use crate::{Direction, Network, Node};
+
+// Colors for each ghost's cycle, reused if there are more ghosts than colors
+const GHOST_COLORS: [&str; 8] = ["red", "blue", "darkgreen", "darkorange", "purple", "brown", "deeppink", "teal"];
+
+impl<'a> Network<'a> {
+    // Writes the network as a Graphviz digraph: one edge per direction labelled L or R, start nodes
+    // green, goal nodes red, and the edges of each ghost's cycle drawn bold in that ghost's color
+    pub fn write_dot(
+        &self,
+        out: &mut impl Write,
+        is_start: impl Fn(&Node<'a>) -> bool,
+        is_end: impl Fn(&Node<'a>) -> bool,
+        directions: &[Direction],
+    ) -> Result<()> {
+        let ends: Vec<bool> = self.nodes.iter().map(&is_end).collect();
+        let mut cycle_colors: BTreeMap<(u32, Direction), Vec<&str>> = BTreeMap::new();
+        for (ghost, start) in self.indices_where(&is_start).into_iter().enumerate() {
+            let color = GHOST_COLORS[ghost % GHOST_COLORS.len()];
+            for edge in self.cycle_edges(start, directions, &ends) {
+                cycle_colors.entry(edge).or_default().push(color);
+            }
+        }
+
+        writeln!(out, "digraph network {{")?;
+        for (node, is_end) in self.nodes.iter().zip(&ends) {
+            if is_start(node) {
+                writeln!(out, "    \"{}\" [style=filled, fillcolor=palegreen];", node.0)?;
+            } else if *is_end {
+                writeln!(out, "    \"{}\" [style=filled, fillcolor=lightcoral];", node.0)?;
+            }
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            for (direction, label) in [(Direction::Left, "L"), (Direction::Right, "R")] {
+                let to = self.nodes[self.step(index as u32, direction) as usize];
+                write!(out, "    \"{}\" -> \"{}\" [label=\"{label}\"", node.0, to.0)?;
+                if let Some(colors) = cycle_colors.get(&(index as u32, direction)) {
+                    write!(out, ", color=\"{}\", penwidth=2", colors.join(":"))?;
+                }
+                writeln!(out, "];")?;
+            }
+        }
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+
+    // Every (node, direction) taken during the cycle of the ghost starting at `start`
+    fn cycle_edges(&self, start: u32, directions: &[Direction], ends: &[bool]) -> Vec<(u32, Direction)> {
+        let path = self.find_paths(start, directions, ends);
+        let mut index = start;
+        let mut edges = Vec::with_capacity(path.cycle);
+        for (step, direction) in directions.iter().cycle().take(path.prefix + path.cycle).enumerate() {
+            if step >= path.prefix {
+                edges.push((index, *direction));
+            }
+            index = self.step(index, *direction);
+        }
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Network, parse_directions};
+
+    #[test]
+    fn example() {
+        let input = include_str!("../example.txt");
+        let (directions, body) = input.split_once('\n').unwrap();
+        let directions = parse_directions(directions).unwrap();
+        let network = Network::parse(body.trim()).unwrap();
+        let mut out = Vec::new();
+        network.write_dot(&mut out, |n| n.0.ends_with('A'), |n| n.0.ends_with('Z'), &directions).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = dot.lines().collect();
+        assert_eq!(lines[0], "digraph network {");
+        assert!(lines.contains(&"    \"11A\" [style=filled, fillcolor=palegreen];"));
+        assert!(lines.contains(&"    \"22Z\" [style=filled, fillcolor=lightcoral];"));
+        assert!(lines.contains(&"    \"11A\" -> \"11B\" [label=\"L\"];"));
+        assert!(lines.contains(&"    \"11B\" -> \"11Z\" [label=\"R\", color=\"red\", penwidth=2];"));
+        assert!(lines.contains(&"    \"11Z\" -> \"11B\" [label=\"L\", color=\"red\", penwidth=2];"));
+        assert!(lines.contains(&"    \"22B\" -> \"22C\" [label=\"L\", color=\"blue\", penwidth=2];"));
+        assert!(lines.contains(&"    \"22B\" -> \"22C\" [label=\"R\", color=\"blue\", penwidth=2];"));
+        assert_eq!(lines.iter().filter(|l| l.contains("->")).count(), 2 * 8);
+        assert_eq!(lines.last(), Some(&"}"));
+    }
+}
@@ -1,13 +1,17 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Formatter};
-use std::{fs, iter};
-use std::cmp::max;
-use prime_factorization::Factorization;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
 use sdk::*;
 use sdk::anyhow::anyhow;
 
+mod dot;
+
 fn main() -> Result<()> {
     init();
+    let dot_path = parse_args(std::env::args().skip(1))?;
     info!("Hello world");
     let input = fs::read_to_string("day08_haunted_wasteland/input.txt")?;
     let (directions, body) = input.split_once('\n').ok_or(anyhow!("Unexpected input format - unable to split directions from network"))?;
@@ -21,161 +25,178 @@ fn main() -> Result<()> {
     info!("Steps from AAA to ZZZ: {steps:?}");
 
     // Part 2
-    let distances = network.compute_distances(|n| n.0.ends_with('A'), |n| n.0.ends_with('Z'), &directions);
-    let all_steps: Vec<_> = distances.into_iter()
-        .map(|(_, distances)| {
-            if distances.len() > 1 {
-                panic!("TODO");
-            }
-            distances[0] as u64
-        })
-        .collect();
-    let lcm = least_common_multiple(&all_steps);
-    info!("Least common multiple of distances from each node to XXZ: {lcm:?}");
+    let paths = network.compute_distances(|n| n.0.ends_with('A'), |n| n.0.ends_with('Z'), &directions);
+    let paths: Vec<_> = paths.into_values().collect();
+    let steps = first_simultaneous_arrival(&paths)?;
+    info!("Steps until every ghost is on a ..Z node: {steps:?}");
+
+    if let Some(path) = dot_path {
+        let mut out = BufWriter::new(File::create(&path)?);
+        network.write_dot(&mut out, |n| n.0.ends_with('A'), |n| n.0.ends_with('Z'), &directions)?;
+        info!("Wrote network to {}", path.display());
+    }
 
     Ok(())
 }
 
+// Where to write the network as Graphviz DOT, if anywhere
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<Option<PathBuf>> {
+    let mut dot_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" => dot_path = Some(PathBuf::from(args.next().ok_or(anyhow!("Missing value for {arg}"))?)),
+            other => return Err(anyhow!("Unexpected argument `{other}`")),
+        }
+    }
+    Ok(dot_path)
+}
+
+// The network compiled down to node indices: `adjacency[i]` holds the (left, right) indices for
+// `nodes[i]`, so walking it never has to look a node up by name
 #[derive(Clone)]
 struct Network<'a> {
     nodes: Vec<Node<'a>>,
-    edges: BTreeMap<Node<'a>, (usize, usize)>,
+    indices: BTreeMap<Node<'a>, u32>,
+    adjacency: Vec<(u32, u32)>,
 }
 
 impl<'a> Network<'a> {
     fn parse(input: &'a str) -> Result<Self> {
-        let mut nodes = BTreeMap::new();
+        let mut nodes = Vec::new();
+        let mut indices = BTreeMap::new();
         let mut edges = BTreeMap::new();
 
-        let mut index = 0_usize;
         let mut add_node = |node| {
-            *nodes.entry(node).or_insert_with(|| {
-                let i = index;
-                index += 1;
-                i
+            *indices.entry(node).or_insert_with(|| {
+                nodes.push(node);
+                (nodes.len() - 1) as u32
             })
         };
 
         for line in input.lines() {
             let (node, node_edges) = line.split_once(" = ").ok_or(anyhow!("Unable to parse node/edges from {line}"))?;
             let node = Node(node);
-            add_node(node);
+            let index = add_node(node);
 
             let (left, right) = node_edges.trim_start_matches("(").trim_end_matches(")").split_once(", ")
                 .ok_or(anyhow!("Unable to parse edges from {node_edges}"))?;
             let left_index = add_node(Node(left));
             let right_index = add_node(Node(right));
-            edges.insert(node, (left_index, right_index));
+            if edges.insert(index, (left_index, right_index)).is_some() {
+                return Err(anyhow!("Node {node:?} is defined more than once"));
+            }
         }
-        let mut nodes_list = vec![Node(""); nodes.len()];
-        for (node, i) in nodes {
-            nodes_list[i] = node;
+        let adjacency = (0..nodes.len() as u32)
+            .map(|i| edges.get(&i).copied().ok_or_else(|| anyhow!("Node {:?} has no edges", nodes[i as usize])))
+            .collect::<Result<_>>()?;
+        Ok(Network { nodes, indices, adjacency })
+    }
+
+    fn index(&self, node: &Node) -> Option<u32> {
+        self.indices.get(node).copied()
+    }
+
+    fn step(&self, index: u32, direction: Direction) -> u32 {
+        let (left, right) = self.adjacency[index as usize];
+        match direction {
+            Direction::Left => left,
+            Direction::Right => right,
         }
-        Ok(Network { nodes: nodes_list, edges })
+    }
+
+    fn indices_where(&self, predicate: impl Fn(&Node<'a>) -> bool) -> Vec<u32> {
+        (0..self.nodes.len() as u32).filter(|i| predicate(&self.nodes[*i as usize])).collect()
     }
 
     #[allow(unused)]
-    fn traverse(&'a self, start: Node<'a>, directions: impl IntoIterator<Item=Direction>) -> Node<'a> {
-        let mut node = start;
-        for direction in directions.into_iter() {
-            match direction {
-                Direction::Left => {
-                    node = *self.left(&node);
-                }
-                Direction::Right => {
-                    node = *self.left(&node);
-                }
-            }
+    fn traverse(&self, start: Node<'a>, directions: impl IntoIterator<Item=Direction>) -> Option<Node<'a>> {
+        let mut index = self.index(&start)?;
+        for direction in directions {
+            index = self.step(index, direction);
         }
-        node
+        Some(self.nodes[index as usize])
     }
 
     // Part 1
-    fn traverse_until(&'a self, is_start: impl Fn(&Node<'a>) -> bool, is_end: impl Fn(&Node<'a>) -> bool, directions: impl IntoIterator<Item=Direction>) -> Option<usize> {
-        let mut steps = 0;
-        let starts: Vec<_> = self.nodes.iter().filter(|n| is_start(n)).copied().collect();
-        let mut nodes: Vec<_> = starts.clone();
+    fn traverse_until(&self, is_start: impl Fn(&Node<'a>) -> bool, is_end: impl Fn(&Node<'a>) -> bool, directions: impl IntoIterator<Item=Direction>) -> Option<usize> {
+        let starts = self.indices_where(is_start);
+        let ends: Vec<bool> = self.nodes.iter().map(is_end).collect();
+        let mut indices = starts.clone();
         trace!("Starts: {starts:?}");
-        for direction in directions.into_iter() {
-            if nodes.iter().all(|n| is_end(n)) {
+        for (steps, direction) in directions.into_iter().enumerate() {
+            if indices.iter().all(|i| ends[*i as usize]) {
                 return Some(steps);
             }
             if steps > 0 {
-                if let Some((i, n)) = nodes.iter().enumerate().find(|(i, n)| **n == starts[*i]) {
-                    trace!("Node {n:?} back at start (start {i}) after {steps} steps");
+                if let Some((i, n)) = indices.iter().enumerate().find(|(i, n)| **n == starts[*i]) {
+                    trace!("Node {:?} back at start (start {i}) after {steps} steps", self.nodes[*n as usize]);
                     return None;
                 }
             }
 
-            for node in &mut nodes {
-                *node = match direction {
-                    Direction::Left => *self.left(&node),
-                    Direction::Right => *self.right(&node),
-                }
+            for index in &mut indices {
+                *index = self.step(*index, direction);
             }
-            steps += 1;
             if starts.len() > 1 {
-                trace!("After {direction:?}: {nodes:?}");
+                trace!("After {direction:?}: {indices:?}");
             }
         }
         None
     }
 
-    fn find_paths(&'a self, mut node: Node<'a>, directions: &[Direction], is_end: impl Fn(&Node<'a>) -> bool) -> Vec<usize> {
-        let start = node;
-        let mut paths = Vec::new();
+    // Follows the directions from `start` until it's somewhere it's been before at the same point in the
+    // directions, from which point it can only repeat itself
+    fn find_paths(&self, start: u32, directions: &[Direction], ends: &[bool]) -> GhostPath {
+        // Without directions the ghost stays where it starts
+        if directions.is_empty() {
+            let goals = if ends[start as usize] { vec![0] } else { vec![] };
+            return GhostPath { prefix: 0, cycle: 1, goals };
+        }
+        let mut index = start;
+        let mut goals = Vec::new();
         let mut steps = 0;
-        // history of our traversal, with each node and where in the directions we were when we got there
-        let mut history: BTreeSet<(Node<'a>, usize)> = BTreeSet::new();
+        // history of our traversal: the step we first got to each node at each point in the directions,
+        // indexed by node index * directions.len() + direction index
+        let mut history: Vec<Option<usize>> = vec![None; self.nodes.len() * directions.len()];
         loop {
             for (i, direction) in directions.iter().enumerate() {
-                if is_end(&node) {
-                    paths.push(steps);
-                }
-                if !history.insert((node, i)) {
-                    debug!("Paths for {start:?}: {paths:?}");
+                let seen = &mut history[index as usize * directions.len() + i];
+                if let Some(first_seen) = *seen {
                     // we have looped and are done
-                    return paths;
+                    let path = GhostPath { prefix: first_seen, cycle: steps - first_seen, goals };
+                    debug!("Path for {:?}: {path:?}", self.nodes[start as usize]);
+                    return path;
+                }
+                *seen = Some(steps);
+                if ends[index as usize] {
+                    goals.push(steps);
                 }
-                node = match direction {
-                    Direction::Left => *self.left(&node),
-                    Direction::Right => *self.right(&node),
-                };
+                index = self.step(index, *direction);
                 steps += 1;
             }
         }
     }
 
     // Part 2
-    fn compute_distances(&'a self, is_start: impl Fn(&Node<'a>) -> bool, is_end: impl Fn(&Node<'a>) -> bool, directions: &[Direction]) -> BTreeMap<Node, Vec<usize>> {
-        let paths: BTreeMap<Node, Vec<usize>> = self.nodes
-            .iter()
-            .filter(|n| is_start(n))
-            .map(|n| (*n, self.find_paths(*n, directions, &is_end)))
+    fn compute_distances(&self, is_start: impl Fn(&Node<'a>) -> bool, is_end: impl Fn(&Node<'a>) -> bool, directions: &[Direction]) -> BTreeMap<Node<'a>, GhostPath> {
+        let ends: Vec<bool> = self.nodes.iter().map(is_end).collect();
+        let paths: BTreeMap<Node, GhostPath> = self.indices_where(is_start)
+            .into_iter()
+            .map(|i| (self.nodes[i as usize], self.find_paths(i, directions, &ends)))
             .collect();
         debug!("Paths: {paths:?}");
         paths
     }
-
-    fn left(&self, node: &Node<'a>) -> &Node {
-        let (left, _) = self.edges.get(node).unwrap();
-        &self.nodes[*left]
-    }
-
-    fn right(&self, node: &Node) -> &Node {
-        let (_, right) = self.edges.get(node).unwrap();
-        &self.nodes[*right]
-    }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq)]
 struct Node<'a>(&'a str);
 
-impl<'a> Debug for Network<'a> {
+impl Debug for Network<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Network {{")?;
-        for node in &self.nodes {
-            writeln!(f, "    {node:?}: ({:?}, {:?})", self.left(node), self.right(node))?;
+        for (node, (left, right)) in self.nodes.iter().zip(&self.adjacency) {
+            writeln!(f, "    {node:?}: ({:?}, {:?})", self.nodes[*left as usize], self.nodes[*right as usize])?;
         }
         write!(f, "}}")?;
         Ok(())
@@ -183,10 +204,14 @@ impl<'a> Debug for Network<'a> {
 }
 
 fn parse_directions(line: &str) -> Result<Vec<Direction>> {
-    line.trim().chars().map(Direction::try_from).collect()
+    let directions: Vec<Direction> = line.trim().chars().map(Direction::try_from).collect::<Result<_>>()?;
+    if directions.is_empty() {
+        return Err(anyhow!("No directions to follow"));
+    }
+    Ok(directions)
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum Direction {
     Left,
     Right,
@@ -232,19 +257,223 @@ impl<'a, T> Iterator for ListLoop<'a, T> {
     }
 }
 
-pub fn least_common_multiple(numbers: &[u64]) -> u64 {
-    let mut total_factors = BTreeMap::new();
-    for n in numbers {
-        let mut factor_counts: BTreeMap<u64, u64> = BTreeMap::new();
-        let factors = Factorization::<u64>::run(*n);
-        for factor in factors.factors {
-            let count = factor_counts.entry(factor).or_default();
-            *count = *count + 1;
+// The shape of one ghost's walk: `prefix` steps in, it enters a loop of `cycle` steps that it
+// repeats forever. `goals` are the steps (before `prefix + cycle`) at which it's on a goal node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GhostPath {
+    prefix: usize,
+    cycle: usize,
+    goals: Vec<usize>,
+}
+
+impl GhostPath {
+    fn at_goal(&self, step: u64) -> bool {
+        let step = if step < (self.prefix + self.cycle) as u64 {
+            step as usize
+        } else {
+            self.prefix + ((step - self.prefix as u64) % self.cycle as u64) as usize
+        };
+        self.goals.binary_search(&step).is_ok()
+    }
+
+    // Goal steps within the loop, each of which recurs every `cycle` steps
+    fn cycle_goals(&self) -> impl Iterator<Item=usize> + '_ {
+        self.goals.iter().copied().filter(|g| *g >= self.prefix)
+    }
+}
+
+// The first step at which every ghost is on a goal at once, if there is one. Before the slowest ghost
+// has reached its loop, steps are simply checked one at a time. After that, each ghost is on a goal
+// exactly when the step is congruent to one of its loop's goal steps modulo its loop length, so each
+// combination of goal steps is solved with the Chinese remainder theorem.
+fn first_simultaneous_arrival(paths: &[GhostPath]) -> Result<Option<u64>> {
+    let Some(settled) = paths.iter().map(|p| p.prefix as u64).max() else {
+        return Ok(None);
+    };
+    if let Some(step) = (0..settled).find(|step| paths.iter().all(|p| p.at_goal(*step))) {
+        return Ok(Some(step));
+    }
+
+    // (residue, modulus) for each combination of goal steps seen so far
+    let mut congruences: Vec<(u128, u128)> = vec![(0, 1)];
+    for path in paths {
+        let cycle = path.cycle as u128;
+        let mut combined = Vec::new();
+        for congruence in &congruences {
+            for goal in path.cycle_goals() {
+                combined.extend(combine_congruences(*congruence, (goal as u128 % cycle, cycle))?);
+            }
         }
-        for (factor, count) in factor_counts {
-            let current_count = total_factors.entry(factor).or_default();
-            *current_count = max(count, *current_count);
+        congruences = combined;
+        congruences.sort();
+        congruences.dedup();
+        trace!("{} combinations after {path:?}", congruences.len());
+    }
+
+    let settled = settled as u128;
+    let mut first = None;
+    for (residue, modulus) in congruences {
+        let step = if residue >= settled {
+            Some(residue)
+        } else {
+            (settled - residue).div_ceil(modulus).checked_mul(modulus).and_then(|s| s.checked_add(residue))
+        };
+        let step = step
+            .and_then(|step| u64::try_from(step).ok())
+            .ok_or(anyhow!("First arrival for cycle {modulus} after step {settled} doesn't fit in u64"))?;
+        first = Some(first.map_or(step, |first: u64| first.min(step)));
+    }
+    Ok(first)
+}
+
+// Solves x ≡ a (mod m) and x ≡ b (mod n) for moduli that needn't be coprime, giving x's residue
+// modulo lcm(m, n), or `None` if no x satisfies both. The lcm has to fit in an i128.
+fn combine_congruences((a, m): (u128, u128), (b, n): (u128, u128)) -> Result<Option<(u128, u128)>> {
+    let too_large = || anyhow!("Combined cycle length of {m} and {n} steps is too large");
+    let (Ok(m_signed), Ok(n_signed)) = (i128::try_from(m), i128::try_from(n)) else {
+        return Err(too_large());
+    };
+    let (g, p, _) = extended_gcd(m_signed, n_signed);
+    // residues are below their moduli, so this can't overflow
+    let difference = b as i128 - a as i128;
+    if difference % g != 0 {
+        return Ok(None);
+    }
+    let lcm = (m / g as u128)
+        .checked_mul(n)
+        .filter(|lcm| i128::try_from(*lcm).is_ok())
+        .ok_or_else(too_large)?;
+    // m * p ≡ g (mod n), so a + m * k with k ≡ p * (difference / g) (mod n / g) satisfies both
+    let n_over_g = n_signed / g;
+    let k = mul_mod(
+        (difference / g).rem_euclid(n_over_g) as u128,
+        p.rem_euclid(n_over_g) as u128,
+        n_over_g as u128,
+    );
+    // k < n / g, so a + m * k < m + lcm - m
+    Ok(Some(((a + m * k) % lcm, lcm)))
+}
+
+// a * b mod m without overflowing, for m up to i128::MAX
+fn mul_mod(mut a: u128, mut b: u128, m: u128) -> u128 {
+    let mut product = 0;
+    a %= m;
+    while b > 0 {
+        if b & 1 == 1 {
+            product = (product + a) % m;
         }
+        a = (a + a) % m;
+        b >>= 1;
+    }
+    product
+}
+
+// (gcd(a, b), x, y) with a * x + b * y = gcd(a, b)
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{combine_congruences, first_simultaneous_arrival, GhostPath, ListLoop, Network, Node, parse_directions};
+
+    const SIMPLE: &str = "AAA = (BBB, CCC)\nBBB = (DDD, EEE)\nCCC = (ZZZ, GGG)\nDDD = (DDD, DDD)\nEEE = (EEE, EEE)\nGGG = (GGG, GGG)\nZZZ = (ZZZ, ZZZ)";
+
+    #[test]
+    fn traverse() {
+        let network = Network::parse(SIMPLE).unwrap();
+        let directions = parse_directions("RL").unwrap();
+        assert_eq!(network.traverse(Node("AAA"), directions.iter().copied()), Some(Node("ZZZ")));
+        let directions = parse_directions("LR").unwrap();
+        assert_eq!(network.traverse(Node("AAA"), directions.iter().copied()), Some(Node("EEE")));
+        assert_eq!(network.traverse(Node("QQQ"), directions.iter().copied()), None);
+
+        let steps = network.traverse_until(|n| n == &Node("AAA"), |n| n == &Node("ZZZ"), ListLoop::new(&parse_directions("RL").unwrap()).cloned());
+        assert_eq!(steps, Some(2));
+
+        assert!(parse_directions("").is_err());
+        assert!(parse_directions(" \n").is_err());
+        assert!(parse_directions("LXR").is_err());
+        let paths = network.compute_distances(|n| n == &Node("AAA") || n == &Node("ZZZ"), |n| n == &Node("ZZZ"), &[]);
+        assert_eq!(paths[&Node("AAA")], GhostPath { prefix: 0, cycle: 1, goals: vec![] });
+        assert_eq!(paths[&Node("ZZZ")], GhostPath { prefix: 0, cycle: 1, goals: vec![0] });
+        let mut out = Vec::new();
+        network.write_dot(&mut out, |n| n == &Node("AAA"), |n| n == &Node("ZZZ"), &[]).unwrap();
+
+        assert!(Network::parse("AAA = (BBB, AAA)").is_err());
+        assert!(Network::parse("AAA = (AAA, AAA)\nAAA = (AAA, AAA)").is_err());
+    }
+
+    #[test]
+    fn example() {
+        let input = include_str!("../example.txt");
+        let (directions, body) = input.split_once('\n').unwrap();
+        let directions = parse_directions(directions).unwrap();
+        let network = Network::parse(body.trim()).unwrap();
+        let paths = network.compute_distances(|n| n.0.ends_with('A'), |n| n.0.ends_with('Z'), &directions);
+        let paths: Vec<_> = paths.into_values().collect();
+        assert_eq!(paths, vec![
+            GhostPath { prefix: 1, cycle: 2, goals: vec![2] },
+            GhostPath { prefix: 1, cycle: 6, goals: vec![3, 6] },
+        ]);
+        assert_eq!(first_simultaneous_arrival(&paths).unwrap(), Some(6));
+    }
+
+    #[test]
+    fn congruences() {
+        assert_eq!(combine_congruences((2, 3), (3, 5)).unwrap(), Some((8, 15)));
+        assert_eq!(combine_congruences((1, 4), (3, 6)).unwrap(), Some((9, 12)));
+        assert_eq!(combine_congruences((1, 4), (2, 6)).unwrap(), None);
+        assert_eq!(combine_congruences((0, 1), (3, 7)).unwrap(), Some((3, 7)));
+
+        // Moduli near 2^64 whose product stays within an i128
+        let m = (1 << 64) - 59;
+        let n = (1 << 62) - 57;
+        let (x, lcm) = combine_congruences((5, m), (7, n)).unwrap().unwrap();
+        assert_eq!(lcm, m * n);
+        assert_eq!((x % m, x % n), (5, 7));
+        // and ones whose lcm doesn't
+        assert!(combine_congruences((0, m), (0, (1 << 64) - 95)).is_err());
+        assert!(combine_congruences((0, u128::MAX), (0, 2)).is_err());
+    }
+
+    #[test]
+    fn arrivals() {
+        // Only meet before either is looping
+        let paths = [
+            GhostPath { prefix: 5, cycle: 2, goals: vec![3] },
+            GhostPath { prefix: 4, cycle: 3, goals: vec![3, 5] },
+        ];
+        assert_eq!(first_simultaneous_arrival(&paths).unwrap(), Some(3));
+
+        // Goals offset from the start of each loop: 5 + 3k and 7 + 5k first meet at 17
+        let paths = [
+            GhostPath { prefix: 2, cycle: 3, goals: vec![5] },
+            GhostPath { prefix: 6, cycle: 5, goals: vec![0, 7] },
+        ];
+        assert_eq!(first_simultaneous_arrival(&paths).unwrap(), Some(17));
+        for step in 0..17 {
+            assert!(!paths.iter().all(|p| p.at_goal(step)), "{step}");
+        }
+
+        // Even and odd steps never meet
+        let paths = [
+            GhostPath { prefix: 0, cycle: 2, goals: vec![0] },
+            GhostPath { prefix: 0, cycle: 2, goals: vec![1] },
+        ];
+        assert_eq!(first_simultaneous_arrival(&paths).unwrap(), None);
+        assert_eq!(first_simultaneous_arrival(&[]).unwrap(), None);
+
+        // Coprime cycles whose combined length overflows
+        let primes = [1_000_003, 1_000_033, 1_000_037, 1_000_039, 1_000_081, 1_000_099, 1_000_117];
+        let paths: Vec<_> = primes.iter().map(|p| GhostPath { prefix: 0, cycle: *p, goals: vec![p - 1] }).collect();
+        assert!(first_simultaneous_arrival(&paths[..2]).unwrap().is_some());
+        assert!(first_simultaneous_arrival(&paths[..4]).is_err());
+        assert!(first_simultaneous_arrival(&paths).is_err());
     }
-    total_factors.into_iter().flat_map(|(factor, count)| iter::repeat(factor).take(count as usize)).product()
 }
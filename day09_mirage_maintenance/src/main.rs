@@ -1,25 +1,48 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 use anyhow::anyhow;
 use itertools::Itertools;
 use winnow::ascii::{dec_int};
 use winnow::combinator::{separated};
 use winnow::{Parser, PResult};
 use sdk::*;
+use crate::rational::Rational;
+
+mod rational;
 
 fn main() -> Result<()> {
     init();
     info!("Hello, world!");
     let mut nexts_sum = 0;
     let mut prevs_sum = 0;
-    for line in lines("day09_mirage_maintenance/input.txt")? {
-        let line = line?;
-        let history = parse_history.parse(&line)
+    let mut degrees: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut unresolved = Vec::new();
+    for (number, line) in lines("day09_mirage_maintenance/input.txt")?.enumerate() {
+        let values = parse_history.parse(&line)
             .map_err(|e| anyhow!(e.to_string()))?;
-        let (prev, next) = extrapolate(&history);
-        debug!("Prev for {history:?}: {prev}");
-        debug!("Next for {history:?}: {next}");
+        let history = History::new(values)
+            .map_err(|e| anyhow!("History on line {}: {e}", number + 1))?;
+        let degree = match history.degree() {
+            Ok(degree) => degree,
+            Err(e) => {
+                debug!("History on line {}: {e}", number + 1);
+                unresolved.push(number + 1);
+                continue;
+            }
+        };
+        *degrees.entry(degree).or_default() += 1;
+        debug!("Line {}: degree {degree}, p(x) = {}", number + 1, Polynomial(history.coefficients()?));
+        let prev = history.extrapolate_backward(1)?[0];
+        let next = history.extrapolate_forward(1)?[0];
+        debug!("Prev for {:?}: {prev}", history.values());
+        debug!("Next for {:?}: {next}", history.values());
         prevs_sum += prev;
         nexts_sum += next;
     }
+    info!("Histories by degree: {degrees:?}");
+    if !unresolved.is_empty() {
+        warn!("Skipped {} histories whose differences never reach all zeros, on lines {unresolved:?}", unresolved.len());
+    }
     // 1702218515
     info!("Sum of prev values: {prevs_sum}");
     info!("Sum of next values: {nexts_sum}");
@@ -27,44 +50,294 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn parse_history<'a>(input: &mut &'a str) -> PResult<Vec<i64>> {
+fn parse_history(input: &mut &str) -> PResult<Vec<i64>> {
     separated(0.., dec_int::<_, i64, _>, ' ').parse_next(input)
 }
 
-fn extrapolate(history: &[i64]) -> (i64, i64) {
-    if history.is_empty() {
-        panic!("Empty history");
+// A sequence of readings along with its table of differences: `differences[0]` is the readings
+// themselves and each following row holds the differences between neighbours in the row above,
+// down to the first row that's all zeros (or a single value if it never gets there)
+#[derive(Debug, Clone)]
+struct History {
+    differences: Vec<Vec<i64>>,
+}
+
+impl History {
+    fn new(values: Vec<i64>) -> std::result::Result<Self, HistoryError> {
+        if values.is_empty() {
+            return Err(HistoryError::Empty);
+        }
+        let mut differences = vec![values];
+        loop {
+            let last = differences.last().unwrap();
+            if last.len() == 1 || last.iter().all(|d| *d == 0) {
+                break;
+            }
+            let next = last.iter().tuple_windows()
+                .map(|(a, b)| b.checked_sub(*a))
+                .collect::<Option<_>>()
+                .ok_or(HistoryError::OutOfRange { at: None })?;
+            differences.push(next);
+        }
+        trace!("{differences:?}");
+        Ok(History { differences })
     }
-    fn differences(history: &[i64]) -> Vec<i64> {
-        history.iter().tuple_windows().map(|(a, b)| b - a).collect()
+
+    fn values(&self) -> &[i64] {
+        &self.differences[0]
     }
 
-    let mut current = history.to_vec();
-    let mut prev_stack = Vec::new();
-    let mut next_stack = Vec::new();
-    trace!("{history:?}");
-    loop {
-        current = differences(&current);
-        trace!("{current:?}");
-        if current.iter().all(|d| *d == 0) {
-            break;
+    // Degree of the polynomial the readings follow: the row above the first all-zero row. Histories
+    // that run out of differences before reaching one don't determine a polynomial.
+    fn degree(&self) -> std::result::Result<usize, HistoryError> {
+        let last = self.differences.last().unwrap();
+        if last.iter().all(|d| *d == 0) {
+            Ok(self.differences.len().saturating_sub(2))
+        } else {
+            Err(HistoryError::NoZeroRow { rows: self.differences.len() })
         }
-        let Some((first, last)) = current.first().zip(current.last()) else {
-            warn!("Empty differences for {history:?}");
-            break;
-        };
-        prev_stack.push(*first);
-        next_stack.push(*last);
     }
 
-    let next = history.last().unwrap() + next_stack.iter().sum::<i64>();
-    let prev = history.first().unwrap() - prev_stack.iter().enumerate().fold(0, |sum, (i, next)| {
-        if i % 2 == 0 {
-            sum + *next
-        } else {
-            sum - *next
+    // Value of the readings' polynomial at `x` (reading 0 is at 0), from Newton's forward difference
+    // formula: the sum of C(x, k) times the first k-th difference
+    fn evaluate(&self, x: Rational) -> std::result::Result<Rational, HistoryError> {
+        let degree = self.degree()?;
+        let out_of_range = HistoryError::OutOfRange { at: Some(x) };
+        let mut value = Rational::ZERO;
+        let mut binomial = Rational::ONE;
+        for (k, row) in self.differences.iter().take(degree + 1).enumerate() {
+            value = binomial
+                .checked_mul(Rational::from(row[0]))
+                .and_then(|term| value.checked_add(term))
+                .ok_or(out_of_range)?;
+            if k < degree {
+                binomial = x
+                    .checked_sub(Rational::from(k as i64))
+                    .and_then(|factor| binomial.checked_mul(factor))
+                    .and_then(|b| b.checked_div(Rational::from(k as i64 + 1)))
+                    .ok_or(out_of_range)?;
+            }
+        }
+        Ok(value)
+    }
+
+    // Coefficients of the readings' polynomial, constant term first, found by expanding each C(x, k)
+    // of Newton's formula into powers of x
+    fn coefficients(&self) -> std::result::Result<Vec<Rational>, HistoryError> {
+        let degree = self.degree()?;
+        let out_of_range = HistoryError::OutOfRange { at: None };
+        let mut coefficients = vec![Rational::ZERO; degree + 1];
+        // x(x - 1)...(x - k + 1) / k!, constant term first
+        let mut binomial = vec![Rational::ONE];
+        for (k, row) in self.differences.iter().take(degree + 1).enumerate() {
+            for (coefficient, b) in coefficients.iter_mut().zip(&binomial) {
+                *coefficient = b
+                    .checked_mul(Rational::from(row[0]))
+                    .and_then(|term| coefficient.checked_add(term))
+                    .ok_or(out_of_range)?;
+            }
+            if k == degree {
+                break;
+            }
+            let k_plus_one = Rational::from(k as i64 + 1);
+            let k = Rational::from(k as i64);
+            let mut next = vec![Rational::ZERO; binomial.len() + 1];
+            for (i, b) in binomial.iter().enumerate() {
+                next[i + 1] = b
+                    .checked_div(k_plus_one)
+                    .and_then(|term| next[i + 1].checked_add(term))
+                    .ok_or(out_of_range)?;
+                next[i] = b
+                    .checked_mul(k)
+                    .and_then(|term| term.checked_div(k_plus_one))
+                    .and_then(|term| next[i].checked_sub(term))
+                    .ok_or(out_of_range)?;
+            }
+            binomial = next;
+        }
+        Ok(coefficients)
+    }
+
+    // The same value by Lagrange interpolation through the first `degree + 1` readings
+    #[allow(unused)]
+    fn evaluate_lagrange(&self, x: Rational) -> std::result::Result<Rational, HistoryError> {
+        let degree = self.degree()?;
+        let out_of_range = HistoryError::OutOfRange { at: Some(x) };
+        let points: Vec<Rational> = (0..=degree as i64).map(Rational::from).collect();
+        let mut value = Rational::ZERO;
+        for (i, (xi, yi)) in points.iter().zip(self.values()).enumerate() {
+            let mut basis = Rational::ONE;
+            for (_, xj) in points.iter().enumerate().filter(|(j, _)| *j != i) {
+                basis = x
+                    .checked_sub(*xj)
+                    .and_then(|numerator| Some((numerator, xi.checked_sub(*xj)?)))
+                    .and_then(|(numerator, denominator)| basis.checked_mul(numerator)?.checked_div(denominator))
+                    .ok_or(out_of_range)?;
+            }
+            value = basis
+                .checked_mul(Rational::from(*yi))
+                .and_then(|term| value.checked_add(term))
+                .ok_or(out_of_range)?;
         }
-    });
+        Ok(value)
+    }
+
+    // The reading that would be at `index`, which may be before the first or after the last
+    fn value_at(&self, index: i64) -> std::result::Result<i64, HistoryError> {
+        let at = Rational::from(index);
+        let value = self.evaluate(at)?;
+        value
+            .to_integer()
+            .and_then(|v| i64::try_from(v).ok())
+            .ok_or(HistoryError::OutOfRange { at: Some(at) })
+    }
+
+    // The next `steps` readings after the last one
+    fn extrapolate_forward(&self, steps: usize) -> std::result::Result<Vec<i64>, HistoryError> {
+        let len = self.values().len() as i64;
+        (len..len + steps as i64).map(|i| self.value_at(i)).collect()
+    }
+
+    // The `steps` readings before the first one, nearest first
+    fn extrapolate_backward(&self, steps: usize) -> std::result::Result<Vec<i64>, HistoryError> {
+        (1..=steps as i64).map(|i| self.value_at(-i)).collect()
+    }
+}
+
+// Polynomial coefficients, constant term first, displayed as e.g. `1/2x^2 + 3/2x + 1`
+struct Polynomial(Vec<Rational>);
 
-    (prev, next)
-}
\ No newline at end of file
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut terms = self.0.iter().enumerate().rev().filter(|(_, c)| **c != Rational::ZERO).peekable();
+        if terms.peek().is_none() {
+            return write!(f, "0");
+        }
+        let mut first = true;
+        for (power, coefficient) in terms {
+            let text = coefficient.to_string();
+            let (sign, magnitude) = match text.strip_prefix('-') {
+                Some(magnitude) => ("-", magnitude),
+                None => ("+", text.as_str()),
+            };
+            match (first, sign) {
+                (true, "-") => write!(f, "-")?,
+                (true, _) => {}
+                (false, sign) => write!(f, " {sign} ")?,
+            }
+            first = false;
+            match power {
+                0 => write!(f, "{magnitude}")?,
+                _ if magnitude != "1" => write!(f, "{magnitude}x")?,
+                _ => write!(f, "x")?,
+            }
+            if power > 1 {
+                write!(f, "^{power}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HistoryError {
+    Empty,
+    // The differences ran down to a single value without a row of zeros
+    NoZeroRow { rows: usize },
+    // The value at `at` (or the differences or coefficients, for `None`) doesn't fit: differences and
+    // extrapolated readings must fit in an i64 and the exact arithmetic getting there in an i128 fraction
+    OutOfRange { at: Option<Rational> },
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::Empty => write!(f, "History is empty"),
+            HistoryError::NoZeroRow { rows } => {
+                write!(f, "Differences never reach all zeros ({rows} rows down to a single value)")
+            }
+            HistoryError::OutOfRange { at: Some(at) } => write!(f, "Value at {at} is out of range"),
+            HistoryError::OutOfRange { at: None } => write!(f, "Differences or polynomial coefficients are out of range"),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+#[cfg(test)]
+mod tests {
+    use winnow::Parser;
+    use crate::{History, HistoryError, parse_history, Polynomial};
+    use crate::rational::Rational;
+
+    #[test]
+    fn example() {
+        let histories: Vec<_> = include_str!("../example.txt")
+            .lines()
+            .map(|line| History::new(parse_history.parse(line).unwrap()).unwrap())
+            .collect();
+        let nexts: Vec<_> = histories.iter().map(|h| h.extrapolate_forward(1).unwrap()[0]).collect();
+        let prevs: Vec<_> = histories.iter().map(|h| h.extrapolate_backward(1).unwrap()[0]).collect();
+        assert_eq!(nexts, vec![18, 28, 68]);
+        assert_eq!(prevs, vec![-3, 0, 5]);
+        assert_eq!(histories[1].extrapolate_forward(3).unwrap(), vec![28, 36, 45]);
+        assert_eq!(histories[1].extrapolate_backward(3).unwrap(), vec![0, 0, 1]);
+        assert_eq!(histories[2].value_at(2).unwrap(), 16);
+    }
+
+    #[test]
+    fn newton_and_lagrange_agree() {
+        let history = History::new(vec![10, 13, 16, 21, 30, 45]).unwrap();
+        for x in [Rational::new(-7, 2), Rational::new(1, 3), Rational::from(4_i64), Rational::from(100_i64)] {
+            assert_eq!(history.evaluate(x).unwrap(), history.evaluate_lagrange(x).unwrap(), "{x}");
+        }
+        // 10 + 3x + 2 C(x, 3) at 1/2
+        assert_eq!(history.evaluate(Rational::new(1, 2)).unwrap(), Rational::new(93, 8));
+    }
+
+    #[test]
+    fn closed_form() {
+        let history = History::new(vec![1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(history.degree(), Ok(2));
+        let coefficients = history.coefficients().unwrap();
+        assert_eq!(coefficients, vec![Rational::ONE, Rational::new(3, 2), Rational::new(1, 2)]);
+        assert_eq!(Polynomial(coefficients).to_string(), "1/2x^2 + 3/2x + 1");
+
+        let history = History::new(vec![10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(history.degree(), Ok(3));
+        let coefficients = history.coefficients().unwrap();
+        assert_eq!(Polynomial(coefficients.clone()).to_string(), "1/3x^3 - x^2 + 11/3x + 10");
+        // the coefficients reproduce every reading
+        for (x, value) in history.values().iter().enumerate() {
+            let x = Rational::from(x as i64);
+            let at = coefficients.iter().rev().fold(Rational::ZERO, |sum, c| sum.checked_mul(x).unwrap().checked_add(*c).unwrap());
+            assert_eq!(at, Rational::from(*value));
+        }
+
+        assert_eq!(History::new(vec![0, 0]).unwrap().degree(), Ok(0));
+        assert_eq!(Polynomial(History::new(vec![0, 0]).unwrap().coefficients().unwrap()).to_string(), "0");
+        assert_eq!(Polynomial(History::new(vec![-4, -5, -6]).unwrap().coefficients().unwrap()).to_string(), "-x - 4");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(History::new(vec![]).unwrap_err(), HistoryError::Empty);
+        assert_eq!(History::new(vec![i64::MIN, i64::MAX]).unwrap_err(), HistoryError::OutOfRange { at: None });
+        // The first differences fit, the second ones don't
+        assert_eq!(History::new(vec![0, i64::MAX, 0]).unwrap_err(), HistoryError::OutOfRange { at: None });
+        let history = History::new(vec![1, 2, 4, 8]).unwrap();
+        assert_eq!(history.extrapolate_forward(1), Err(HistoryError::NoZeroRow { rows: 4 }));
+        let history = History::new(vec![i64::MAX - 2, i64::MAX - 1, i64::MAX]).unwrap();
+        assert_eq!(history.extrapolate_forward(1), Err(HistoryError::OutOfRange { at: Some(Rational::from(3_i64)) }));
+        // Degree 18, so far enough out even the exact arithmetic overflows rather than just the result
+        let history = History::new([vec![0; 18], vec![1, 19, 190, 1330]].concat()).unwrap();
+        assert_eq!(history.degree(), Ok(18));
+        assert_eq!(history.value_at(22).unwrap(), 7315);
+        assert_eq!(history.value_at(10_000), Err(HistoryError::OutOfRange { at: Some(Rational::from(10_000_i64)) }));
+        assert!(history.extrapolate_forward(20_000).is_err());
+        assert!(history.evaluate_lagrange(Rational::from(10_000_i64)).is_err());
+        // A single reading is a constant, but there's nothing to show its differences are zero
+        assert!(History::new(vec![5]).unwrap().degree().is_err());
+        assert_eq!(History::new(vec![5, 5]).unwrap().extrapolate_forward(2).unwrap(), vec![5, 5]);
+    }
+}
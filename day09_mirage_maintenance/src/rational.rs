@@ -0,0 +1,133 @@
+use std::fmt::{Display, Formatter};
+
+// An exact fraction, always kept in lowest terms with a positive denominator so equal values compare
+// equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { numerator: 0, denominator: 1 };
+    pub const ONE: Rational = Rational { numerator: 1, denominator: 1 };
+
+    // For known-good values; arithmetic goes through the `checked_*` methods
+    #[allow(unused)]
+    pub fn new(numerator: i128, denominator: i128) -> Self {
+        Rational::reduced(numerator, denominator).expect("Rational with zero denominator or out of range")
+    }
+
+    // In lowest terms with a positive denominator, or `None` if the denominator is zero or the
+    // result doesn't fit
+    fn reduced(numerator: i128, denominator: i128) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = i128::try_from(gcd(numerator.unsigned_abs(), denominator.unsigned_abs())).ok()?;
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        if denominator < 0 {
+            Some(Rational { numerator: numerator.checked_neg()?, denominator: denominator.checked_neg()? })
+        } else {
+            Some(Rational { numerator, denominator })
+        }
+    }
+
+    pub fn to_integer(self) -> Option<i128> {
+        (self.denominator == 1).then_some(self.numerator)
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let divisor = i128::try_from(gcd(self.denominator.unsigned_abs(), rhs.denominator.unsigned_abs())).ok()?;
+        let denominator = (self.denominator / divisor).checked_mul(rhs.denominator)?;
+        let numerator = self.numerator
+            .checked_mul(denominator / self.denominator)?
+            .checked_add(rhs.numerator.checked_mul(denominator / rhs.denominator)?)?;
+        Rational::reduced(numerator, denominator)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs.checked_neg()?)
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        // cross-cancel first to keep the intermediates small
+        let a = i128::try_from(gcd(self.numerator.unsigned_abs(), rhs.denominator.unsigned_abs())).ok()?;
+        let b = i128::try_from(gcd(rhs.numerator.unsigned_abs(), self.denominator.unsigned_abs())).ok()?;
+        Rational::reduced(
+            (self.numerator / a).checked_mul(rhs.numerator / b)?,
+            (self.denominator / b).checked_mul(rhs.denominator / a)?,
+        )
+    }
+
+    // `None` when dividing by zero, as well as when the result doesn't fit
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        self.checked_mul(Rational::reduced(rhs.denominator, rhs.numerator)?)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Rational { numerator: self.numerator.checked_neg()?, denominator: self.denominator })
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+impl From<i64> for Rational {
+    fn from(value: i64) -> Self {
+        Rational { numerator: value as i128, denominator: 1 }
+    }
+}
+
+impl From<i128> for Rational {
+    fn from(value: i128) -> Self {
+        Rational { numerator: value, denominator: 1 }
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rational::Rational;
+
+    #[test]
+    fn arithmetic() {
+        let half = Rational::new(1, 2);
+        let third = Rational::new(-2, -6);
+        assert_eq!(third, Rational::new(1, 3));
+        assert_eq!(half.checked_add(third), Some(Rational::new(5, 6)));
+        assert_eq!(half.checked_sub(third), Some(Rational::new(1, 6)));
+        assert_eq!(half.checked_mul(third), Some(Rational::new(1, 6)));
+        assert_eq!(half.checked_div(third), Some(Rational::new(3, 2)));
+        assert_eq!(Rational::new(4, -2), Rational::from(-2_i64));
+        assert_eq!(Rational::new(4, -2).to_integer(), Some(-2));
+        assert_eq!(half.to_integer(), None);
+        assert_eq!(half.checked_neg().unwrap().to_string(), "-1/2");
+        assert_eq!(half.checked_add(half).unwrap().to_string(), "1");
+        assert_eq!(half.checked_sub(half), Some(Rational::ZERO));
+    }
+
+    #[test]
+    fn overflow() {
+        let big = Rational::from(i128::MAX);
+        assert_eq!(big.checked_add(Rational::ONE), None);
+        assert_eq!(big.checked_mul(Rational::from(2_i64)), None);
+        assert_eq!(Rational::from(i128::MIN).checked_neg(), None);
+        assert_eq!(Rational::ONE.checked_div(Rational::ZERO), None);
+        assert_eq!(Rational::new(1, i128::MAX).checked_add(Rational::new(1, i128::MAX - 1)), None);
+        assert_eq!(big.checked_mul(Rational::new(1, 2)), Some(Rational::new(i128::MAX, 2)));
+    }
+}
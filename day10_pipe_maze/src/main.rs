@@ -1,96 +1,299 @@
 use std::cmp::min;
-use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::{Debug, Display, Formatter};
+use std::io::IsTerminal;
+use std::str::FromStr;
 use sdk::*;
 use sdk::anyhow::anyhow;
+use crate::render::RenderOptions;
+
+mod render;
 
 fn main() -> Result<()> {
     init();
+    let args = Args::parse(std::env::args().skip(1))?;
     let maze = parse_maze(lines("day10_pipe_maze/input.txt")?)?;
     debug!("Maze: {maze}");
     let (farthest_node, dist) = maze.farthest_location();
     info!("Farthest location: {farthest_node} at {dist} steps");
-    let area = maze.interior_area()?;
-    info!("Interior area {area}");
+    info!("Loop of {} tiles winds {:?} with {:?}", maze.cycle().len(), maze.winding(), maze.turns());
+    let inside = maze.inside();
+    let (enclosed, outside): (Vec<Side>, Vec<Side>) = maze.sides().into_values().partition(|side| *side == inside);
+    debug!("{} tiles on the {inside:?} of the walk are enclosed, {} outside", enclosed.len(), outside.len());
+    let area = maze.interior_area_by(args.area)?;
+    info!("Interior area {area} ({})", args.area);
+
+    if args.render || args.heatmap {
+        let options = RenderOptions { color: std::io::stdout().is_terminal(), heatmap: args.heatmap };
+        maze.render(&mut std::io::stdout().lock(), options)?;
+    }
     Ok(())
 }
 
-fn parse_maze(input: impl Iterator<Item=impl AsRef<str>>) -> Result<Maze> {
+#[derive(Debug, Default)]
+struct Args {
+    area: AreaMethod,
+    // Draw the maze to stdout, with colors only when it's a terminal
+    render: bool,
+    // Also shade the loop by distance from the start (implies `render`)
+    heatmap: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item=String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(anyhow!("Missing value for {arg}"));
+            match arg.as_str() {
+                "--area" => parsed.area = AreaMethod::from_str(&value()?)?,
+                "--render" => parsed.render = true,
+                "--heatmap" => parsed.heatmap = true,
+                other => return Err(anyhow!("Unexpected argument `{other}`")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+// Ways of counting the tiles enclosed by the loop, which should all agree
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+enum AreaMethod {
+    // Crossings of the loop along each row
+    #[default]
+    Scanline,
+    // The loop's area from the shoelace formula, less its boundary by Pick's theorem
+    Shoelace,
+    // Filling the outside in on a grid at twice the scale, where gaps between pipes are tiles
+    FloodFill,
+}
+
+impl AreaMethod {
+    const ALL: [AreaMethod; 3] = [AreaMethod::Scanline, AreaMethod::Shoelace, AreaMethod::FloodFill];
+}
+
+impl FromStr for AreaMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        AreaMethod::ALL
+            .into_iter()
+            .find(|method| method.to_string() == s)
+            .ok_or(anyhow!("Unknown area method `{s}`; expected one of scanline, shoelace or flood-fill"))
+    }
+}
+
+impl Display for AreaMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AreaMethod::Scanline => write!(f, "scanline"),
+            AreaMethod::Shoelace => write!(f, "shoelace"),
+            AreaMethod::FloodFill => write!(f, "flood-fill"),
+        }
+    }
+}
+
+fn parse_maze(input: impl Iterator<Item=impl AsRef<str>>) -> std::result::Result<Maze, MazeError> {
     let mut height = 0;
     let mut width = 0;
-    let mut connections: BTreeMap<Coordinates, Vec<Coordinates>> = BTreeMap::new();
-    let mut pipes: BTreeMap<Coordinates, char> = BTreeMap::new();
+    let mut tiles: BTreeMap<Coordinates, char> = BTreeMap::new();
     let mut start = None;
     for (y, line) in input.enumerate() {
         height += 1;
         width = line.as_ref().len();
         for (x, c) in line.as_ref().chars().enumerate() {
-            // Coordinates of neighbors can be negative
-            let x = x as isize;
-            let y = y as isize;
-
             let current = (x, y).into();
-            let new_connections = match c {
-                'S' => {
-                    start = Some(current);
-                    vec![
-                        (x - 1, y),
-                        (x + 1, y),
-                        (x, y - 1),
-                        (x, y + 1),
-                    ]
-                },
-                '-' => vec![(x - 1, y), (x + 1, y)],
-                '|' => vec![(x, y - 1), (x, y + 1)],
-                'F' => vec![(x + 1, y), (x, y + 1)],
-                'J' => vec![(x - 1, y), (x, y - 1)],
-                'L' => vec![(x + 1, y), (x, y - 1)],
-                '7' => vec![(x - 1, y), (x, y + 1)],
-                _ => continue,
-            };
-            pipes.insert(current, c);
-            connections.insert(current, Vec::new());
-            let connections = connections.entry(current).or_default();
-            for neighbor in new_connections.iter().copied() {
-                let neighbor = neighbor.into();
-                if !connections.contains(&neighbor) {
-                    connections.push(neighbor);
-                }
+            if c == 'S' {
+                start = Some(current);
+            } else if pipe_directions(c).is_none() {
+                continue;
             }
+            tiles.insert(current, c);
         }
     }
-    // Remove any one-way connections
-    trace!("Removing one-way connections from {pipes:?}");
-    let mut to_remove = BTreeSet::new();
-    for (pipe, pipe_connections) in connections.iter() {
-        for connection in pipe_connections {
-            let two_way = connections
-                .get(connection)
-                .map(|conns_conns| conns_conns.contains(pipe))
-                .unwrap_or_default();
-            if !two_way {
-                trace!("One-way connection found from {pipe} to {connection}");
-                to_remove.insert((*pipe, *connection));
+    let start = start.ok_or(MazeError::NoStart)?;
+
+    // Only the neighbors whose pipes lead back to the start can be part of the loop. With more than
+    // two of those, each pair is a possible shape for the start and the loop decides between them.
+    let candidates: Vec<Direction> = Direction::ALL
+        .into_iter()
+        .filter(|d| {
+            tiles
+                .get(&start.step(*d))
+                .and_then(|c| pipe_directions(*c))
+                .is_some_and(|directions| directions.contains(&d.opposite()))
+        })
+        .collect();
+    let mut loops = Vec::new();
+    let mut errors = Vec::new();
+    for (i, first) in candidates.iter().enumerate() {
+        for second in &candidates[i + 1..] {
+            let shape = [*first, *second];
+            match trace_loop(&tiles, start, shape) {
+                Ok(path) => loops.push((pipe_shape(shape), path)),
+                Err(e) => {
+                    trace!("Start as {} doesn't close the loop: {e}", pipe_shape(shape));
+                    errors.push(e);
+                }
             }
         }
     }
-    let start = start.ok_or(anyhow!("No start found"))?;
+    let (start_pipe, path) = match loops.len() {
+        1 => loops.pop().unwrap(),
+        0 if errors.len() == 1 => return Err(errors.pop().unwrap()),
+        0 => return Err(MazeError::NoClosedLoop),
+        _ => return Err(MazeError::AmbiguousStart(loops.into_iter().map(|(shape, _)| shape).collect())),
+    };
+    debug!("Start is {start_pipe}, loop is {} pipes long", path.len());
+
+    let mut connections: BTreeMap<Coordinates, Vec<Coordinates>> = BTreeMap::new();
+    for (a, b) in path.iter().zip(path.iter().cycle().skip(1)) {
+        connections.entry(*a).or_default().push(*b);
+        connections.entry(*b).or_default().push(*a);
+    }
+    let pipes = path.iter().map(|c| (*c, tiles[c])).collect();
+    Ok(Maze { height, width, start, start_pipe, pipes, connections, path })
+}
 
-    connections.iter_mut().for_each(|(a, v)| v.retain(|b| !to_remove.contains(&(*a, *b))));
-    let mut main_loop = BTreeSet::new();
-    let mut stack = vec![start];
-    while let Some(next) = stack.pop() {
-        if main_loop.insert(next) {
-            for connection in connections.get(&next).unwrap() {
-                stack.push(*connection);
+// Follows the pipes out of the start in the first direction of `shape` until they lead back in from
+// the second, giving the tiles of the loop in order
+fn trace_loop(tiles: &BTreeMap<Coordinates, char>, start: Coordinates, shape: [Direction; 2]) -> std::result::Result<Vec<Coordinates>, MazeError> {
+    let mut path = vec![start];
+    let mut current = start;
+    let mut heading = shape[0];
+    loop {
+        let next = current.step(heading);
+        if next == start {
+            if heading.opposite() == shape[1] {
+                return Ok(path);
             }
+            return Err(MazeError::DanglingPipe { at: current, toward: heading });
         }
+        let directions = tiles.get(&next).and_then(|c| pipe_directions(*c));
+        let Some([a, b]) = directions.filter(|d| d.contains(&heading.opposite())) else {
+            return Err(MazeError::DanglingPipe { at: current, toward: heading });
+        };
+        heading = if a == heading.opposite() { b } else { a };
+        path.push(next);
+        current = next;
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
 
-    pipes.retain(|c, _| main_loop.contains(c));
-    Ok(Maze { height, width, start, connections, pipes })
+    fn turn_right(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
+
+    fn turn_left(self) -> Direction {
+        self.turn_right().opposite()
+    }
+
+    // The direction of a single step from `from` to `to`
+    fn between(from: Coordinates, to: Coordinates) -> Direction {
+        Direction::ALL
+            .into_iter()
+            .find(|d| from.step(*d) == to)
+            .unwrap_or_else(|| panic!("{from} and {to} aren't neighbors"))
+    }
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
+// The two directions a pipe connects, or `None` if it's not a pipe
+fn pipe_directions(pipe: char) -> Option<[Direction; 2]> {
+    use Direction::*;
+    match pipe {
+        '-' => Some([East, West]),
+        '|' => Some([North, South]),
+        'F' => Some([East, South]),
+        'J' => Some([North, West]),
+        'L' => Some([North, East]),
+        '7' => Some([South, West]),
+        _ => None,
+    }
 }
 
+// The pipe connecting both directions
+fn pipe_shape(mut directions: [Direction; 2]) -> char {
+    use Direction::*;
+    directions.sort();
+    match directions {
+        [North, South] => '|',
+        [East, West] => '-',
+        [East, South] => 'F',
+        [North, West] => 'J',
+        [North, East] => 'L',
+        [South, West] => '7',
+        _ => unreachable!("{directions:?} is not a pipe"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Winding {
+    Clockwise,
+    Counterclockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Turns {
+    left: usize,
+    right: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MazeError {
+    NoStart,
+    // The pipe at `at` leads `toward` a tile that doesn't lead back
+    DanglingPipe { at: Coordinates, toward: Direction },
+    // More than one shape for the start closes a loop
+    AmbiguousStart(Vec<char>),
+    NoClosedLoop,
+}
+
+impl Display for MazeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MazeError::NoStart => write!(f, "No start found"),
+            MazeError::DanglingPipe { at, toward } => {
+                write!(f, "Pipe at {at} leads {toward:?} to a tile that doesn't connect back")
+            }
+            MazeError::AmbiguousStart(shapes) => {
+                write!(f, "Start could be any of {shapes:?}, each of which closes a loop")
+            }
+            MazeError::NoClosedLoop => write!(f, "No shape for the start closes a loop"),
+        }
+    }
+}
+
+impl std::error::Error for MazeError {}
+
 #[derive(Clone, Copy, PartialOrd, PartialEq, Ord, Eq)]
 struct Coordinates {
     x: isize,
@@ -109,6 +312,18 @@ impl From<(usize, usize)> for Coordinates {
     }
 }
 
+impl Coordinates {
+    fn step(self, direction: Direction) -> Coordinates {
+        let Coordinates { x, y } = self;
+        match direction {
+            Direction::North => (x, y - 1),
+            Direction::East => (x + 1, y),
+            Direction::South => (x, y + 1),
+            Direction::West => (x - 1, y),
+        }.into()
+    }
+}
+
 impl Display for Coordinates {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let Coordinates { x, y } = self;
@@ -127,12 +342,25 @@ struct Maze {
     height: usize,
     width: usize,
     start: Coordinates,
+    // The pipe hidden under the start
+    start_pipe: char,
+    // Pipes making up the loop, including the start
     pipes: BTreeMap<Coordinates, char>,
     connections: BTreeMap<Coordinates, Vec<Coordinates>>,
+    // The loop's tiles in order, starting at the start
+    path: Vec<Coordinates>,
 }
 
 impl Maze {
     fn farthest_location(&self) -> (Coordinates, usize) {
+        let mut distances: Vec<_> = self.distances().into_iter().collect();
+        distances.sort_by_key(|(_, dist)| *dist);
+        debug!("distances: {distances:?}");
+        *distances.last().unwrap()
+    }
+
+    // Steps from the start to each tile of the loop, going whichever way round is shorter
+    fn distances(&self) -> BTreeMap<Coordinates, usize> {
         let mut visited: BTreeMap<Coordinates, usize> = BTreeMap::new();
         let mut next = VecDeque::new();
         next.push_back((self.start, 0));
@@ -151,12 +379,148 @@ impl Maze {
                 next.push_back((*connection, dist + 1));
             }
         }
-        let mut distances: Vec<_> = visited.into_iter().collect();
-        distances.sort_by_key(|(_, dist)| *dist);
-        debug!("distances: {distances:?}");
-        print_distances(&distances);
+        visited
+    }
+
+    // The loop's tiles in the order they're walked, starting at the start
+    fn cycle(&self) -> &[Coordinates] {
+        &self.path
+    }
 
-        distances.last().unwrap().clone()
+    // Each step of the walk around the loop as the tile it starts from and the direction it heads in
+    fn headings(&self) -> impl Iterator<Item=(Coordinates, Direction)> + '_ {
+        self.path
+            .iter()
+            .zip(self.path.iter().cycle().skip(1))
+            .map(|(a, b)| (*a, Direction::between(*a, *b)))
+    }
+
+    // Which way the walk goes round, as seen on screen (y increasing downwards)
+    fn winding(&self) -> Winding {
+        // Twice the signed area from the shoelace formula, positive for clockwise with y down
+        let twice_area: isize = self.path
+            .iter()
+            .zip(self.path.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum();
+        if twice_area > 0 {
+            Winding::Clockwise
+        } else {
+            Winding::Counterclockwise
+        }
+    }
+
+    // How many times the walk turns each way. A closed loop always makes four more turns one way
+    // than the other, in the direction it winds.
+    fn turns(&self) -> Turns {
+        let headings: Vec<Direction> = self.headings().map(|(_, d)| d).collect();
+        let mut turns = Turns::default();
+        for (incoming, outgoing) in headings.iter().zip(headings.iter().cycle().skip(1)) {
+            if *outgoing == incoming.turn_right() {
+                turns.right += 1;
+            } else if *outgoing == incoming.turn_left() {
+                turns.left += 1;
+            }
+        }
+        turns
+    }
+
+    // Which side of the walk each tile off the loop is on. Tiles next to the loop are seen directly
+    // while walking it; the rest take the side of whichever of those they can reach without crossing it.
+    fn sides(&self) -> BTreeMap<Coordinates, Side> {
+        let in_maze = |c: &Coordinates| (0..self.width as isize).contains(&c.x) && (0..self.height as isize).contains(&c.y);
+        let off_loop = |c: &Coordinates| in_maze(c) && !self.pipes.contains_key(c);
+        let mut sides = BTreeMap::new();
+        let mut stack = Vec::new();
+        let headings: Vec<_> = self.headings().collect();
+        // Both the heading into and out of each tile see its neighbors, so corners see both sides
+        let previous = headings.iter().cycle().skip(headings.len() - 1).map(|(_, d)| *d);
+        for ((tile, outgoing), incoming) in headings.iter().zip(previous) {
+            for heading in [incoming, *outgoing] {
+                for (side, direction) in [(Side::Left, heading.turn_left()), (Side::Right, heading.turn_right())] {
+                    let neighbor = tile.step(direction);
+                    if off_loop(&neighbor) && sides.insert(neighbor, side).is_none() {
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        while let Some(tile) = stack.pop() {
+            let side = sides[&tile];
+            for direction in Direction::ALL {
+                let neighbor = tile.step(direction);
+                if off_loop(&neighbor) && !sides.contains_key(&neighbor) {
+                    sides.insert(neighbor, side);
+                    stack.push(neighbor);
+                }
+            }
+        }
+        sides
+    }
+
+    // The side of the walk the loop encloses
+    fn inside(&self) -> Side {
+        match self.winding() {
+            Winding::Clockwise => Side::Right,
+            Winding::Counterclockwise => Side::Left,
+        }
+    }
+
+    fn interior_area_by(&self, method: AreaMethod) -> Result<usize> {
+        match method {
+            AreaMethod::Scanline => self.interior_area(),
+            AreaMethod::Shoelace => Ok(self.interior_area_shoelace()),
+            AreaMethod::FloodFill => Ok(self.interior_area_flood_fill()),
+        }
+    }
+
+    fn interior_area_shoelace(&self) -> usize {
+        // Twice the area of the polygon through the middle of each loop tile
+        let twice_area = self.path
+            .iter()
+            .zip(self.path.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum::<isize>()
+            .unsigned_abs();
+        // Pick's theorem: area = interior + boundary / 2 - 1
+        (twice_area + 2 - self.path.len()) / 2
+    }
+
+    fn interior_area_flood_fill(&self) -> usize {
+        // Tile (x, y) becomes cell (2x + 1, 2y + 1), leaving a cell between neighboring tiles for
+        // the pipe joining them (if any) and a border all the way around to start filling from
+        let width = 2 * self.width + 1;
+        let height = 2 * self.height + 1;
+        let cell = |c: Coordinates| (2 * c.y as usize + 1) * width + 2 * c.x as usize + 1;
+        let mut walls = vec![false; width * height];
+        for (a, b) in self.path.iter().zip(self.path.iter().cycle().skip(1)) {
+            walls[cell(*a)] = true;
+            walls[(cell(*a) + cell(*b)) / 2] = true;
+        }
+
+        let mut outside = vec![false; width * height];
+        outside[0] = true;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % width, i / width);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then_some(i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then_some(i + width),
+            ];
+            for neighbor in neighbors.into_iter().flatten() {
+                if !walls[neighbor] && !outside[neighbor] {
+                    outside[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| Coordinates::from((x, y))))
+            .filter(|c| !walls[cell(*c)] && !outside[cell(*c)])
+            .count()
     }
 
     fn interior_area(&self) -> Result<usize> {
@@ -168,9 +532,7 @@ impl Maze {
         }
         use SqueezeDirection::*;
         let mut area = 0;
-        let mut lines = Vec::with_capacity(self.height);
         for y in 0..self.height {
-            let mut line = String::with_capacity(self.width);
             let mut inside = false;
             let mut squeezing = None;
             for x in 0..self.width {
@@ -178,9 +540,8 @@ impl Maze {
                 let coords = (x, y).into();
                 if let Some(mut current) = self.pipes.get(&coords).copied() {
                     if current == 'S' {
-                        current = self.start_char();
+                        current = self.start_pipe;
                     }
-                    line.push(current);
                     match (squeezing, current) {
                         (_, '|') => {
                             inside = !inside;
@@ -215,38 +576,12 @@ impl Maze {
                     squeezing = None;
                     if inside {
                         area += 1;
-                        line.push('I');
-                    } else {
-                        line.push('O');
                     }
                 }
             }
-            lines.push(line);
-        }
-        println!("Inside outside map:");
-        for line in lines {
-            println!("{line}");
         }
         Ok(area)
     }
-
-    fn start_char(&self) -> char {
-        let start = self.start;
-        let connections = self.connections.get(&self.start).expect("Illegal map state");
-        let left = connections.iter().any(|c| c.x < start.x);
-        let right = connections.iter().any(|c| c.x > start.x);
-        let above = connections.iter().any(|c| c.y < start.y);
-        let below = connections.iter().any(|c| c.y > start.y);
-        match (left, right, above, below) {
-            (false, false, true, true) => '|',
-            (false, true, false, true) => 'F',
-            (false, true, true, false) => 'L',
-            (true, false, false, true) => '7',
-            (true, false, true, false) => 'J',
-            (true, true, false, false) => '-',
-            _ => panic!("Illegal map state")
-        }
-    }
 }
 
 impl Display for Maze {
@@ -257,30 +592,116 @@ impl Display for Maze {
                 let char = self.pipes.get(&(x, y).into()).copied().unwrap_or('.');
                 write!(f, "{char}")?;
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
-fn print_distances(distances: &[(Coordinates, usize)]) {
-    let height = distances.iter().map(|(c, _)| c.y).max().unwrap();
-    let width = distances.iter().map(|(c, _)| c.x).max().unwrap();
-    let distances: BTreeMap<Coordinates, usize> = distances.into_iter()
-        .map(|(c, d)| (*c, *d))
-        .collect();
-    let max_dist = distances.values().max().unwrap();
-    let max_dist_size = max_dist.to_string().len();
-    let print_size = max_dist_size + 2;
-    for y in 0..=height {
-        let mut line = String::with_capacity(width as usize);
-        for x in 0..=width {
-            if let Some(dist) = distances.get(&(x, y).into()) {
-                line.push_str(&format!("{:^width$}", dist, width = print_size));
-            } else {
-                line.push_str(&format!("{:.^width$}", ".", width = print_size));
+#[cfg(test)]
+mod tests {
+    use crate::{AreaMethod, Direction, MazeError, parse_maze, Side, Turns, Winding};
+
+    #[test]
+    fn example() {
+        let maze = parse_maze(include_str!("../example.txt").lines()).unwrap();
+        assert_eq!(maze.start_pipe, '7');
+        assert_eq!(maze.farthest_location().1, 80);
+        assert_eq!(maze.interior_area().unwrap(), 10);
+    }
+
+    #[test]
+    fn area_methods_agree() {
+        let squeezed = [
+            "..........",
+            ".S------7.",
+            ".|F----7|.",
+            ".||....||.",
+            ".||....||.",
+            ".|L-7F-J|.",
+            ".|..||..|.",
+            ".L--JL--J.",
+            "..........",
+        ];
+        let mazes = [
+            (parse_maze(include_str!("../example.txt").lines()).unwrap(), 10),
+            (parse_maze(squeezed.into_iter()).unwrap(), 4),
+            (parse_maze(["S7", "LJ"].into_iter()).unwrap(), 0),
+            (parse_maze(["S-7", "|.|", "L-J"].into_iter()).unwrap(), 1),
+        ];
+        for (maze, expected) in mazes {
+            for method in AreaMethod::ALL {
+                assert_eq!(maze.interior_area_by(method).unwrap(), expected, "{method}");
+                assert_eq!(method.to_string().parse::<AreaMethod>().unwrap(), method);
             }
         }
-        println!("{line}");
+    }
+
+    #[test]
+    fn walking_the_loop() {
+        let maze = parse_maze([
+            ".....",
+            ".S-7.",
+            ".|.|.",
+            ".L-J.",
+            ".....",
+        ].into_iter()).unwrap();
+        // The start heads east first, so round clockwise
+        assert_eq!(maze.cycle()[..3], [(1_usize, 1_usize).into(), (2_usize, 1_usize).into(), (3_usize, 1_usize).into()]);
+        assert_eq!(maze.cycle().len(), 8);
+        assert_eq!(maze.winding(), Winding::Clockwise);
+        assert_eq!(maze.turns(), Turns { left: 0, right: 4 });
+        let sides = maze.sides();
+        assert_eq!(sides[&(2_usize, 2_usize).into()], Side::Right);
+        assert_eq!(sides[&(0_usize, 0_usize).into()], Side::Left);
+        assert_eq!(sides[&(4_usize, 2_usize).into()], Side::Left);
+        assert_eq!(sides.len(), 25 - 8);
+
+        let maze = parse_maze(include_str!("../example.txt").lines()).unwrap();
+        let turns = maze.turns();
+        let expected_difference = match maze.winding() {
+            Winding::Clockwise => 4,
+            Winding::Counterclockwise => -4,
+        };
+        assert_eq!(turns.right as isize - turns.left as isize, expected_difference);
+        let inside = maze.sides().values().filter(|side| **side == maze.inside()).count();
+        assert_eq!(inside, 10);
+        assert_eq!(maze.sides().len() + maze.cycle().len(), maze.width * maze.height);
+    }
+
+    #[test]
+    fn start_shapes() {
+        // Three neighbors lead back to the start, but only one pair closes a loop
+        let maze = parse_maze([
+            ".....",
+            ".F7..",
+            "-SJ..",
+            ".....",
+        ].into_iter()).unwrap();
+        assert_eq!(maze.start_pipe, 'L');
+        assert_eq!(maze.pipes.len(), 4);
+
+        // Two loops through the start
+        let error = parse_maze([
+            "..F-7",
+            "..|.|",
+            "F-S-J",
+            "|.|..",
+            "L-J..",
+        ].into_iter()).unwrap_err();
+        assert_eq!(error, MazeError::AmbiguousStart(vec!['L', '7']));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_maze(["F7", "LJ"].into_iter()).unwrap_err(), MazeError::NoStart);
+        let error = parse_maze(["S-7", "|.|", "L-."].into_iter()).unwrap_err();
+        assert_eq!(error, MazeError::DanglingPipe { at: (2_usize, 1_usize).into(), toward: Direction::South });
+        let error = parse_maze(["S-7", "|.|", "L.J"].into_iter()).unwrap_err();
+        assert_eq!(error.to_string(), "Pipe at (2, 2) leads West to a tile that doesn't connect back");
+        // With only one possible shape for the start, its dangling pipe is the error
+        let error = parse_maze(["-S-", "..."].into_iter()).unwrap_err();
+        assert_eq!(error, MazeError::DanglingPipe { at: (2_usize, 0_usize).into(), toward: Direction::East });
+        assert_eq!(parse_maze([".|.", "-S-", ".|."].into_iter()).unwrap_err(), MazeError::NoClosedLoop);
     }
 }
@@ -0,0 +1,97 @@
+use std::io::Write;
+use colored::{ColoredString, Colorize};
+use crate::{Coordinates, Maze};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    // Use terminal colors at all
+    pub color: bool,
+    // Shade each loop tile from blue (near the start) to red (farthest away); needs `color`
+    pub heatmap: bool,
+}
+
+// The box-drawing character for a pipe
+fn box_drawing(pipe: char) -> char {
+    match pipe {
+        '-' => '─',
+        '|' => '│',
+        'F' => '┌',
+        '7' => '┐',
+        'L' => '└',
+        'J' => '┘',
+        other => other,
+    }
+}
+
+impl Maze {
+    // Draws the loop with box-drawing characters, the start as `S` and enclosed tiles as `I`, one line
+    // per row. Tiles outside the loop (including any pipes not on it) are drawn as `.`.
+    pub fn render(&self, out: &mut impl Write, options: RenderOptions) -> std::io::Result<()> {
+        let inside = self.inside();
+        let sides = self.sides();
+        let distances = self.distances();
+        let max_distance = distances.values().copied().max().unwrap_or(0).max(1);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let coordinates: Coordinates = (x, y).into();
+                let (text, styled): (String, ColoredString) = if coordinates == self.start {
+                    ("S".to_string(), "S".bold().black().on_white())
+                } else if let Some(pipe) = self.pipes.get(&coordinates) {
+                    let text = box_drawing(*pipe).to_string();
+                    let styled = match distances.get(&coordinates) {
+                        Some(distance) if options.heatmap => {
+                            let heat = (distance * 255 / max_distance) as u8;
+                            text.truecolor(heat, 64, 255 - heat)
+                        }
+                        _ => text.bold(),
+                    };
+                    (text, styled)
+                } else if sides.get(&coordinates) == Some(&inside) {
+                    ("I".to_string(), "I".green().bold())
+                } else {
+                    (".".to_string(), ".".dimmed())
+                };
+                if options.color {
+                    write!(out, "{styled}")?;
+                } else {
+                    write!(out, "{text}")?;
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_maze;
+    use crate::render::RenderOptions;
+
+    #[test]
+    fn render_plain() {
+        let maze = parse_maze([
+            "-L|F7",
+            "7S-7|",
+            "L|7||",
+            "-L-J|",
+            "L|-JF",
+        ].into_iter()).unwrap();
+        let mut out = Vec::new();
+        maze.render(&mut out, RenderOptions::default()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), [
+            ".....",
+            ".S─┐.",
+            ".│I│.",
+            ".└─┘.",
+            ".....",
+            "",
+        ].join("\n"));
+
+        // The heatmap only changes the colors
+        let mut out = Vec::new();
+        maze.render(&mut out, RenderOptions { color: false, heatmap: true }).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().nth(3), Some(".└─┘."));
+    }
+}
@@ -1,87 +1,268 @@
-use std::collections::BTreeSet;
 use std::fmt::{Display, Formatter};
-use std::mem;
-use itertools::Itertools;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
 use sdk::*;
 use sdk::anyhow::anyhow;
 
 fn main() -> Result<()> {
     init();
-    let mut image = Image::parse(lines("day11_cosmic_expansion/input.txt")?)?;
+    let args = Args::parse(std::env::args().skip(1))?;
+    let image = Image::parse(lines("day11_cosmic_expansion/input.txt")?)?;
     debug!("{image:?}");
     debug!("{image}");
-    image.expand(1000000);
-    if image.width < 1000 && image.height < 1000 {
-        debug!("Expanded: {image}");
-    } else {
-        debug!("Expanded: width: {}, height: {}, galaxies: {}", image.width, image.height, image.galaxies.len());
-    }
-    let sum_of_distances = image.sum_of_distances();
-    info!("Sum of distances: {sum_of_distances}");
+    for factor in &args.factors {
+        let factor = *factor;
+        // A factor too large for the sum is reported without giving up on the others
+        match image.distances_at(factor) {
+            Ok(sum_of_distances) => info!("Sum of distances with expansion factor {factor}: {sum_of_distances}"),
+            Err(e) => error!("{e}"),
+        }
+        if let Some((a, b)) = args.distance {
+            match image.distance_between(a, b, factor) {
+                Ok(distance) => info!("Distance from galaxy {a} to galaxy {b} with expansion factor {factor}: {distance}"),
+                Err(e) => error!("{e}"),
+            }
+        }
+        if let Some(k) = args.nearest {
+            match image.nearest_neighbors(k, factor) {
+                Ok(nearest) => {
+                    for (galaxy, neighbors) in nearest.iter().enumerate() {
+                        info!("Nearest galaxies to {} with expansion factor {factor}: {neighbors:?}", galaxy + 1);
+                    }
+                }
+                Err(e) => error!("{e}"),
+            }
+        }
+    }
+    if let Some(path) = &args.matrix {
+        let [factor] = args.factors[..] else {
+            return Err(anyhow!("Exporting the distance matrix needs exactly one expansion factor"));
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        image.write_distance_matrix(&mut out, factor)?;
+        info!("Wrote distance matrix with expansion factor {factor} to {}", path.display());
+    }
     Ok(())
 }
 
+#[derive(Debug)]
+struct Args {
+    // How many times larger each empty row and column becomes, one run for each
+    factors: Vec<u64>,
+    // Galaxy numbers (in reading order, from 1) to report the distance between
+    distance: Option<(usize, usize)>,
+    // How many of each galaxy's nearest neighbors to report
+    nearest: Option<usize>,
+    // Where to write every distance between galaxies as CSV
+    matrix: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        // Parts 1 and 2
+        Args { factors: vec![2, 1_000_000], distance: None, nearest: None, matrix: None }
+    }
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item=String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(anyhow!("Missing value for {arg}"));
+            match arg.as_str() {
+                "--factors" => {
+                    parsed.factors = value()?.split(',').map(|f| u64::from_str(f.trim())).collect::<std::result::Result<_, _>>()?;
+                    if parsed.factors.contains(&0) {
+                        return Err(anyhow!("Expansion factors must be at least 1"));
+                    }
+                }
+                "--distance" => {
+                    let value = value()?;
+                    let (a, b) = value.split_once(',').ok_or(anyhow!("Expected two galaxy numbers, e.g. 5,9, not {value}"))?;
+                    parsed.distance = Some((usize::from_str(a.trim())?, usize::from_str(b.trim())?));
+                }
+                "--nearest" => parsed.nearest = Some(usize::from_str(&value()?)?),
+                "--matrix" => parsed.matrix = Some(PathBuf::from(value()?)),
+                other => return Err(anyhow!("Unexpected argument `{other}`")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Image {
     width: usize,
     height: usize,
-    galaxies: BTreeSet<(usize, usize)>
+    // (x, y) of each galaxy in reading order, so galaxy n (counting from 1) is at `galaxies[n - 1]`
+    galaxies: Vec<(usize, usize)>,
+    // Number of empty columns to the left of each column, and of empty rows above each row
+    empty_columns_before: Vec<u64>,
+    empty_rows_before: Vec<u64>,
 }
 
 impl Image {
     fn parse(input: impl Iterator<Item=String>) -> Result<Self> {
         let mut width = 0;
         let mut height = 0;
-        let mut galaxies = BTreeSet::new();
+        let mut galaxies = Vec::new();
         for (y, line) in input.enumerate() {
-            width = line.len();
+            if y == 0 {
+                width = line.len();
+            } else if line.len() != width {
+                return Err(anyhow!("Row {} is {} wide, but the image is {width} wide", y + 1, line.len()));
+            }
             height += 1;
             for (x, char) in line.chars().enumerate() {
                 match char {
                     '#' => {
-                        galaxies.insert((x, y));
+                        galaxies.push((x, y));
                     },
                     '.' => {}
                     _ => return Err(anyhow!("Unepected character in input: {char}"))
                 }
             }
         }
-        Ok(Image { width, height, galaxies })
+        let (columns, rows): (Vec<usize>, Vec<usize>) = galaxies.iter().copied().unzip();
+        let empty_columns_before = empty_before(width, &columns);
+        let empty_rows_before = empty_before(height, &rows);
+        Ok(Image { width, height, galaxies, empty_columns_before, empty_rows_before })
     }
 
-    fn expand(&mut self, factor: usize) {
-        let mut galactic_columns = BTreeSet::new();
-        let mut galactic_rows = BTreeSet::new();
-        for (x, y) in &self.galaxies {
-            galactic_columns.insert(*x);
-            galactic_rows.insert(*y);
-        }
-        let expanding_columns: BTreeSet<_> = (0..self.width).filter(|c| !galactic_columns.contains(c)).collect();
-        let expanding_rows: BTreeSet<_> = (0..self.height).filter(|r| !galactic_rows.contains(r)).collect();
+    // Sum of the distances between every pair of galaxies once each empty row and column has become
+    // `factor` of them. Distances along each axis are independent, so each is summed separately.
+    fn distances_at(&self, factor: u64) -> Result<u64> {
+        let (columns, rows): (Vec<usize>, Vec<usize>) = self.galaxies.iter().copied().unzip();
+        axis_distance_sum(&columns, &self.empty_columns_before, factor)
+            .zip(axis_distance_sum(&rows, &self.empty_rows_before, factor))
+            .and_then(|(columns, rows)| columns.checked_add(rows))
+            .and_then(|sum| u64::try_from(sum).ok())
+            .ok_or(anyhow!("Sum of distances with expansion factor {factor} exceeds u64"))
+    }
 
-        // Replace each row with <factor> rows (the `-1` accounts for removing the original row)
-        self.width += expanding_columns.len() * (factor - 1);
-        self.height += expanding_rows.len() * (factor - 1);
+    // Where a galaxy ends up once each empty row and column has become `factor` of them, if that's
+    // representable
+    fn expanded(&self, (x, y): (usize, usize), factor: u64) -> Result<(u64, u64)> {
+        let expand = |line: usize, empty_before: u64| {
+            empty_before
+                .checked_mul(factor - 1)
+                .and_then(|extra| extra.checked_add(line as u64))
+                .ok_or(anyhow!("Galaxy at ({x}, {y}) is out of range with expansion factor {factor}"))
+        };
+        Ok((expand(x, self.empty_columns_before[x])?, expand(y, self.empty_rows_before[y])?))
+    }
 
-        for (x, y) in mem::take(&mut self.galaxies) {
-            let column_expansion = expanding_columns.iter().filter(|c| **c < x).count();
-            let row_expansion = expanding_rows.iter().filter(|r| **r < y).count();
-            self.galaxies.insert((x + (column_expansion * (factor - 1)), y + (row_expansion * (factor - 1))));
-        }
+    fn expanded_galaxies(&self, factor: u64) -> Result<Vec<(u64, u64)>> {
+        self.galaxies.iter().map(|g| self.expanded(*g, factor)).collect()
     }
 
-    fn sum_of_distances(&self) -> usize {
-        self.galaxies
-            .iter()
+    fn galaxy(&self, number: usize) -> Result<(usize, usize)> {
+        number
+            .checked_sub(1)
+            .and_then(|i| self.galaxies.get(i))
             .copied()
-            .tuple_combinations()
-            .map(|(a, b)| distance(a, b))
-            .sum()
+            .ok_or(anyhow!("No galaxy {number}; galaxies are numbered 1 to {}", self.galaxies.len()))
+    }
+
+    fn distance_between(&self, a: usize, b: usize, factor: u64) -> Result<u64> {
+        distance(self.expanded(self.galaxy(a)?, factor)?, self.expanded(self.galaxy(b)?, factor)?)
+    }
+
+    // For each galaxy in order, the numbers of and distances to its `k` nearest galaxies, nearest
+    // first (ties going to the lower number)
+    fn nearest_neighbors(&self, k: usize, factor: u64) -> Result<Vec<Vec<(usize, u64)>>> {
+        let expanded = self.expanded_galaxies(factor)?;
+        expanded
+            .iter()
+            .enumerate()
+            .map(|(i, from)| {
+                let mut neighbors: Vec<(usize, u64)> = expanded
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(j, to)| Ok((j + 1, distance(*from, *to)?)))
+                    .collect::<Result<_>>()?;
+                neighbors.sort_by_key(|(number, distance)| (*distance, *number));
+                neighbors.truncate(k);
+                Ok(neighbors)
+            })
+            .collect()
+    }
+
+    // Writes the distance between every pair of galaxies as CSV, with a header row and column of
+    // galaxy numbers. Refuses images with so many galaxies that the matrix would be unwieldy.
+    fn write_distance_matrix(&self, out: &mut impl Write, factor: u64) -> Result<()> {
+        if self.galaxies.len() > MAX_MATRIX_GALAXIES {
+            return Err(anyhow!(
+                "{} galaxies is too many for a distance matrix (at most {MAX_MATRIX_GALAXIES})",
+                self.galaxies.len()
+            ));
+        }
+        let expanded = self.expanded_galaxies(factor)?;
+        write!(out, "galaxy")?;
+        for number in 1..=expanded.len() {
+            write!(out, ",{number}")?;
+        }
+        writeln!(out)?;
+        for (i, from) in expanded.iter().enumerate() {
+            write!(out, "{}", i + 1)?;
+            for to in &expanded {
+                write!(out, ",{}", distance(*from, *to)?)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
     }
 }
 
-fn distance((from_x, from_y): (usize, usize), (to_x, to_y): (usize, usize)) -> usize {
-    from_x.abs_diff(to_x) + from_y.abs_diff(to_y)
+// Manhattan distance between two expanded positions, if it fits
+fn distance(a: (u64, u64), b: (u64, u64)) -> Result<u64> {
+    a.0.abs_diff(b.0)
+        .checked_add(a.1.abs_diff(b.1))
+        .ok_or(anyhow!("Distance from {a:?} to {b:?} exceeds u64"))
+}
+
+const MAX_MATRIX_GALAXIES: usize = 1000;
+
+// How many lines before each of `len` lines have no galaxies in them
+fn empty_before(len: usize, occupied: &[usize]) -> Vec<u64> {
+    let mut has_galaxy = vec![false; len];
+    for line in occupied {
+        has_galaxy[*line] = true;
+    }
+    let mut empty = 0;
+    has_galaxy
+        .into_iter()
+        .map(|has_galaxy| {
+            let before = empty;
+            if !has_galaxy {
+                empty += 1;
+            }
+            before
+        })
+        .collect()
+}
+
+// Sum of the distances between every pair of positions along one axis after expansion. Once sorted,
+// the distances from the i-th position back to the i before it add up to `position * i` less their sum.
+// Worked in u128, where any u64 factor fits: positions stay below 2^72 for puzzle-sized images and
+// the sum below 2^110 for up to 2^18 galaxies. Anything bigger is `None` rather than wrapping.
+fn axis_distance_sum(lines: &[usize], empty_before: &[u64], factor: u64) -> Option<u128> {
+    let mut positions: Vec<u128> = lines
+        .iter()
+        .map(|line| *line as u128 + empty_before[*line] as u128 * (factor as u128 - 1))
+        .collect();
+    positions.sort_unstable();
+    let mut before: u128 = 0;
+    let mut sum: u128 = 0;
+    for (i, position) in positions.into_iter().enumerate() {
+        // Sorted, so `position * i` is never less than the positions before it
+        sum = position.checked_mul(i as u128).and_then(|p| sum.checked_add(p - before))?;
+        before = before.checked_add(position)?;
+    }
+    Some(sum)
 }
 
 impl Display for Image {
@@ -89,14 +270,126 @@ impl Display for Image {
         writeln!(f, "Image:")?;
         for y in 0..self.height {
             for x in 0..self.width {
-                if self.galaxies.contains(&(x, y)) {
+                if self.galaxies.binary_search_by_key(&(y, x), |(x, y)| (*y, *x)).is_ok() {
                     write!(f, "#")?;
                 } else {
                     write!(f, ".")?;
                 }
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use crate::{axis_distance_sum, Args, Image};
+
+    #[test]
+    fn example() {
+        let image = Image::parse(include_str!("../example.txt").lines().map(String::from)).unwrap();
+        assert_eq!(image.distances_at(2).unwrap(), 374);
+        assert_eq!(image.distances_at(10).unwrap(), 1030);
+        assert_eq!(image.distances_at(100).unwrap(), 8410);
+    }
+
+    #[test]
+    fn axis_overflow() {
+        assert_eq!(axis_distance_sum(&[0, 1, 3], &[0, 0, 1, 1], 2), Some(8));
+        // Two galaxies pushed almost 2^128 along, each that far from the one at the start
+        assert_eq!(axis_distance_sum(&[0, 1, 1], &[0, u64::MAX], u64::MAX), None);
+    }
+
+    #[test]
+    fn ragged() {
+        let error = Image::parse(["..#", "."].into_iter().map(String::from)).unwrap_err();
+        assert_eq!(error.to_string(), "Row 2 is 1 wide, but the image is 3 wide");
+        assert!(Image::parse([".", "..#"].into_iter().map(String::from)).is_err());
+        assert!(Image::parse(["#.", ".#"].into_iter().map(String::from)).is_ok());
+    }
+
+    #[test]
+    fn huge_factors() {
+        let image = Image::parse(include_str!("../example.txt").lines().map(String::from)).unwrap();
+        // The sum grows by 82 for every extra line each empty line expands to: 8410 = 292 + 82 * 99
+        let factor = 100_000_000_000_000;
+        assert_eq!(image.distances_at(factor).unwrap(), 292 + 82 * (factor - 1));
+        let error = image.distances_at(u64::MAX).unwrap_err();
+        assert_eq!(error.to_string(), format!("Sum of distances with expansion factor {} exceeds u64", u64::MAX));
+    }
+
+    #[test]
+    fn queries() {
+        let image = Image::parse(include_str!("../example.txt").lines().map(String::from)).unwrap();
+        assert_eq!(image.galaxies[..3], [(3, 0), (7, 1), (0, 2)]);
+        assert_eq!(image.distance_between(5, 9, 2).unwrap(), 9);
+        assert_eq!(image.distance_between(1, 7, 2).unwrap(), 15);
+        assert_eq!(image.distance_between(3, 6, 2).unwrap(), 17);
+        assert_eq!(image.distance_between(8, 9, 2).unwrap(), 5);
+        assert_eq!(image.distance_between(9, 8, 2).unwrap(), 5);
+        assert!(image.distance_between(0, 1, 2).is_err());
+        assert!(image.distance_between(1, 10, 2).is_err());
+
+        let nearest = image.nearest_neighbors(2, 2).unwrap();
+        assert_eq!(nearest.len(), 9);
+        assert_eq!(nearest[7], vec![(9, 5), (5, 6)]);
+        for (i, neighbors) in nearest.iter().enumerate() {
+            for (number, distance) in neighbors {
+                assert_eq!(image.distance_between(i + 1, *number, 2).unwrap(), *distance);
+            }
+        }
+
+        let mut out = Vec::new();
+        image.write_distance_matrix(&mut out, 2).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "galaxy,1,2,3,4,5,6,7,8,9");
+        assert_eq!(lines[8].split(',').nth(9), Some("5"));
+        assert_eq!(lines[5].split(',').nth(9), Some("9"));
+
+        // Galaxies 1 and 2 are one empty column apart, and every galaxy is past at least one empty
+        // line, so none of their positions fit at the largest factor
+        let factor = u64::MAX / 4;
+        assert_eq!(image.distance_between(1, 2, factor).unwrap(), 5 + (factor - 1));
+        assert!(image.distance_between(1, 2, u64::MAX).is_err());
+        assert!(image.nearest_neighbors(2, u64::MAX).is_err());
+        assert!(image.write_distance_matrix(&mut Vec::new(), u64::MAX).is_err());
+    }
+
+    #[test]
+    fn factors() {
+        assert_eq!(Args::parse(std::iter::empty()).unwrap().factors, vec![2, 1_000_000]);
+        let args = Args::parse(["--factors", "18446744073709551615"].into_iter().map(String::from)).unwrap();
+        assert_eq!(args.factors, vec![u64::MAX]);
+        assert!(Args::parse(["--factors", "18446744073709551616"].into_iter().map(String::from)).is_err());
+        let args = Args::parse(["--factors", "1, 10,100"].into_iter().map(String::from)).unwrap();
+        assert_eq!(args.factors, vec![1, 10, 100]);
+        assert!(Args::parse(["--factors", "0"].into_iter().map(String::from)).is_err());
+        assert!(Args::parse(["--factors"].into_iter().map(String::from)).is_err());
+    }
+
+    #[test]
+    fn matches_pairwise() {
+        let image = Image::parse(include_str!("../example.txt").lines().map(String::from)).unwrap();
+        let empty_columns: Vec<_> = (0..image.width).filter(|x| image.galaxies.iter().all(|g| g.0 != *x)).collect();
+        let empty_rows: Vec<_> = (0..image.height).filter(|y| image.galaxies.iter().all(|g| g.1 != *y)).collect();
+        for factor in [1, 2, 7, 1_000_000] {
+            let expanded: Vec<_> = image.galaxies
+                .iter()
+                .map(|(x, y)| {
+                    let x = *x as u64 + empty_columns.iter().filter(|c| **c < *x).count() as u64 * (factor - 1);
+                    let y = *y as u64 + empty_rows.iter().filter(|r| **r < *y).count() as u64 * (factor - 1);
+                    (x, y)
+                })
+                .collect();
+            let pairwise: u64 = expanded
+                .iter()
+                .tuple_combinations()
+                .map(|(a, b)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1))
+                .sum();
+            assert_eq!(image.distances_at(factor).unwrap(), pairwise, "{factor}");
+        }
+    }
+}